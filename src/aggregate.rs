@@ -0,0 +1,343 @@
+use crate::error::{Error, Result};
+use crate::sort::sort_rows;
+use crate::table::Table;
+use crate::workbook::{cell_number, cell_to_string};
+use calamine::Data;
+use std::collections::{HashMap, HashSet};
+
+// Name of the sheet the summary is written to
+pub const SUMMARY_SHEET: &str = "Summary";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateFn {
+    Sum,
+    Count,
+    Avg,
+    Min,
+    Max,
+    Distinct,
+}
+
+impl AggregateFn {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sum" => Some(AggregateFn::Sum),
+            "count" => Some(AggregateFn::Count),
+            "avg" | "average" | "mean" => Some(AggregateFn::Avg),
+            "min" => Some(AggregateFn::Min),
+            "max" => Some(AggregateFn::Max),
+            "distinct" | "count_distinct" => Some(AggregateFn::Distinct),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AggregateFn::Sum => "Sum",
+            AggregateFn::Count => "Count",
+            AggregateFn::Avg => "Average",
+            AggregateFn::Min => "Min",
+            AggregateFn::Max => "Max",
+            AggregateFn::Distinct => "Distinct Count",
+        }
+    }
+}
+
+// One summary column, e.g. sum(Amount). `column` is None for count(*).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Aggregate {
+    pub function: AggregateFn,
+    pub column: Option<String>,
+    // Header for the summary column; defaults to e.g. "Sum of Amount"
+    pub header: Option<String>,
+}
+
+impl Aggregate {
+    // `column_header` is the header of the resolved value column
+    fn header(&self, column_header: Option<&str>) -> String {
+        match (&self.header, column_header) {
+            (Some(header), _) => header.clone(),
+            (None, Some(column)) => format!("{} of {}", self.function.label(), column),
+            (None, None) => self.function.label().to_string(),
+        }
+    }
+}
+
+// Group rows by key columns and summarise each group. No key columns gives
+// a single grand-total row; no aggregates counts the rows of each group.
+#[derive(Clone, Debug, Default)]
+pub struct GroupBy {
+    pub columns: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+// Parse the key columns of a group-by, e.g. "Region,Status"
+pub fn parse_group_by(list: &str) -> Result<Vec<String>> {
+    let columns: Vec<String> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+        .collect();
+    if columns.is_empty() {
+        return Err(Error::Invalid("Group-by column list is empty".to_string()));
+    }
+    Ok(columns)
+}
+
+// Parse an aggregate list such as "sum(Amount),count(*),avg(Amount):Mean".
+// Functions are sum, count, avg, min, max and distinct; a trailing ":Header"
+// names the summary column.
+pub fn parse_aggregates(list: &str) -> Result<Vec<Aggregate>> {
+    let aggregates = list
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = || Error::Invalid(format!("Invalid aggregate {:?} (expected e.g. sum(Amount))", entry));
+            let (call, header) = match entry.rfind(')') {
+                Some(close) => (&entry[..=close], entry[close + 1..].trim()),
+                None => return Err(invalid()),
+            };
+            let header = match header.strip_prefix(':') {
+                Some(header) if !header.trim().is_empty() => Some(header.trim().to_string()),
+                None if header.is_empty() => None,
+                _ => return Err(invalid()),
+            };
+            let (name, column) = call[..call.len() - 1].split_once('(').ok_or_else(invalid)?;
+            let function = AggregateFn::parse(name.trim())
+                .ok_or_else(|| Error::Invalid(format!("Unknown aggregate function {:?}", name.trim())))?;
+            let column = match column.trim() {
+                "" => return Err(invalid()),
+                "*" if function == AggregateFn::Count => None,
+                "*" => return Err(Error::Invalid(format!("Only count accepts * in {:?}", entry))),
+                column => Some(column.to_string()),
+            };
+            Ok(Aggregate { function, column, header })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if aggregates.is_empty() {
+        return Err(Error::Invalid("Aggregate list is empty".to_string()));
+    }
+    Ok(aggregates)
+}
+
+// Running totals for one aggregate of one group
+#[derive(Default)]
+struct Accumulator {
+    rows: usize,
+    // Non-blank cells
+    count: usize,
+    // Cells that read as numbers
+    numbers: usize,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    distinct: HashSet<String>,
+}
+
+impl Accumulator {
+    fn add(&mut self, function: AggregateFn, cell: Option<&Data>) {
+        self.rows += 1;
+        let Some(cell) = cell else { return };
+        let text = cell_to_string(cell);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        self.count += 1;
+        if function == AggregateFn::Distinct {
+            self.distinct.insert(text.to_string());
+        }
+        if let Some(n) = cell_number(cell) {
+            self.numbers += 1;
+            self.sum += n;
+            self.min = Some(self.min.map_or(n, |min| min.min(n)));
+            self.max = Some(self.max.map_or(n, |max| max.max(n)));
+        }
+    }
+
+    fn result(&self, aggregate: &Aggregate) -> Data {
+        match aggregate.function {
+            AggregateFn::Count if aggregate.column.is_none() => Data::Int(self.rows as i64),
+            AggregateFn::Count => Data::Int(self.count as i64),
+            AggregateFn::Distinct => Data::Int(self.distinct.len() as i64),
+            AggregateFn::Sum => Data::Float(self.sum),
+            AggregateFn::Avg if self.numbers > 0 => Data::Float(self.sum / self.numbers as f64),
+            AggregateFn::Min => self.min.map_or(Data::Empty, Data::Float),
+            AggregateFn::Max => self.max.map_or(Data::Empty, Data::Float),
+            AggregateFn::Avg => Data::Empty,
+        }
+    }
+}
+
+// Build the summary table: one row per distinct key, sorted by key, with the
+// key columns first and one native numeric column per aggregate. Sum, avg,
+// min and max ignore cells that aren't numbers.
+pub fn aggregate_table(table: &Table, group_by: &GroupBy) -> Result<Table> {
+    let keys = group_by
+        .columns
+        .iter()
+        .map(|column| table.require_column(column))
+        .collect::<Result<Vec<_>>>()?;
+    let default_count = [Aggregate {
+        function: AggregateFn::Count,
+        column: None,
+        header: None,
+    }];
+    let aggregates = if group_by.aggregates.is_empty() {
+        &default_count[..]
+    } else {
+        &group_by.aggregates[..]
+    };
+    let values = aggregates
+        .iter()
+        .map(|aggregate| aggregate.column.as_ref().map(|column| table.require_column(column)).transpose())
+        .collect::<Result<Vec<_>>>()?;
+
+    // Groups in order of first appearance, keyed on trimmed text
+    let mut groups: Vec<(Vec<Data>, Vec<Accumulator>)> = Vec::new();
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    for row in &table.rows {
+        let key: Vec<String> = keys.iter().map(|&col| cell_to_string(&row[col]).trim().to_string()).collect();
+        let group = *index.entry(key).or_insert_with(|| {
+            let key_cells = keys.iter().map(|&col| row[col].clone()).collect();
+            groups.push((key_cells, aggregates.iter().map(|_| Accumulator::default()).collect()));
+            groups.len() - 1
+        });
+        for ((accumulator, aggregate), value) in groups[group].1.iter_mut().zip(aggregates).zip(&values) {
+            accumulator.add(aggregate.function, value.map(|col| &row[col]));
+        }
+    }
+    // A grand total still has a row when there is no data
+    if keys.is_empty() && groups.is_empty() {
+        groups.push((Vec::new(), aggregates.iter().map(|_| Accumulator::default()).collect()));
+    }
+
+    let header = keys
+        .iter()
+        .map(|&col| table.header[col].clone())
+        .chain(
+            aggregates
+                .iter()
+                .zip(&values)
+                .map(|(aggregate, value)| aggregate.header(value.map(|col| table.header[col].as_str()))),
+        )
+        .collect();
+    let rows = groups
+        .into_iter()
+        .map(|(mut row, accumulators)| {
+            row.extend(accumulators.iter().zip(aggregates).map(|(acc, aggregate)| acc.result(aggregate)));
+            row
+        })
+        .collect();
+    let sort_columns: Vec<(usize, bool)> = (0..keys.len()).map(|col| (col, false)).collect();
+    Ok(Table {
+        header,
+        rows: sort_rows(rows, &sort_columns, false),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Data {
+        Data::String(s.to_string())
+    }
+
+    // Region, Amount
+    fn table(rows: Vec<(Data, Data)>) -> Table {
+        Table {
+            header: vec!["Region".to_string(), "Amount".to_string()],
+            rows: rows.into_iter().map(|(region, amount)| vec![region, amount]).collect(),
+        }
+    }
+
+    fn summarise(table: &Table, columns: &str, aggregates: &str) -> Table {
+        let group_by = GroupBy {
+            columns: if columns.is_empty() { Vec::new() } else { parse_group_by(columns).unwrap() },
+            aggregates: parse_aggregates(aggregates).unwrap(),
+        };
+        aggregate_table(table, &group_by).unwrap()
+    }
+
+    #[test]
+    fn summarises_each_group() {
+        let table = table(vec![
+            (text("South"), Data::Int(4)),
+            (text("North"), Data::Float(1.5)),
+            (text("South"), Data::Int(2)),
+            (text("North"), text(" 2.5 ")),
+            (text("South"), Data::Int(9)),
+        ]);
+        let summary = summarise(&table, "Region", "sum(Amount),avg(Amount),min(B),max(B),count(Amount)");
+        assert_eq!(
+            summary.header,
+            ["Region", "Sum of Amount", "Average of Amount", "Min of Amount", "Max of Amount", "Count of Amount"]
+        );
+        let floats = |values: [f64; 4]| values.into_iter().map(Data::Float);
+        let north: Vec<Data> = [text("North")].into_iter().chain(floats([4.0, 2.0, 1.5, 2.5])).collect();
+        let south: Vec<Data> = [text("South")].into_iter().chain(floats([15.0, 5.0, 2.0, 9.0])).collect();
+        assert_eq!(summary.rows, [[north, vec![Data::Int(2)]].concat(), [south, vec![Data::Int(3)]].concat()]);
+    }
+
+    #[test]
+    fn numeric_aggregates_skip_cells_that_are_not_numbers() {
+        let table = table(vec![
+            (text("North"), Data::Int(3)),
+            (text("North"), text("n/a")),
+            (text("North"), Data::Empty),
+            (text("North"), text("NaN")),
+            (text("South"), text("pending")),
+        ]);
+        let summary = summarise(&table, "Region", "sum(Amount),avg(Amount),min(Amount),count(Amount),count(*)");
+        assert_eq!(
+            summary.rows,
+            [
+                vec![text("North"), Data::Float(3.0), Data::Float(3.0), Data::Float(3.0), Data::Int(3), Data::Int(4)],
+                vec![text("South"), Data::Float(0.0), Data::Empty, Data::Empty, Data::Int(1), Data::Int(1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_keys_form_one_group() {
+        let table = table(vec![
+            (Data::Empty, Data::Int(1)),
+            (text("North"), Data::Int(2)),
+            (text("  "), Data::Int(3)),
+            (text(" North"), Data::Int(4)),
+        ]);
+        let summary = summarise(&table, "Region", "sum(Amount):Total");
+        assert_eq!(summary.header, ["Region", "Total"]);
+        assert_eq!(
+            summary.rows,
+            [vec![text("North"), Data::Float(6.0)], vec![Data::Empty, Data::Float(4.0)]]
+        );
+    }
+
+    #[test]
+    fn no_key_columns_gives_a_grand_total() {
+        let rows = vec![(text("North"), Data::Int(2)), (text("South"), Data::Int(3))];
+        let summary = summarise(&table(rows), "", "sum(Amount),distinct(Region)");
+        assert_eq!(summary.rows, [vec![Data::Float(5.0), Data::Int(2)]]);
+        let summary = summarise(&table(Vec::new()), "", "count(*),max(Amount)");
+        assert_eq!(summary.rows, [vec![Data::Int(0), Data::Empty]]);
+    }
+
+    #[test]
+    fn rejects_bad_aggregates() {
+        assert!(parse_aggregates("sum(*)").is_err());
+        assert!(parse_aggregates("median(Amount)").is_err());
+        assert!(parse_aggregates("sum Amount").is_err());
+        assert!(parse_aggregates(" , ").is_err());
+        let group_by = GroupBy {
+            columns: vec!["Status".to_string()],
+            aggregates: Vec::new(),
+        };
+        let error = aggregate_table(&table(Vec::new()), &group_by).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown column: Status");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::{Error, Result};
+use crate::paths::{sanitize_file_name, sanitize_relative_path};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use zip::{ZipArchive, ZipWriter};
+
+// Default cap on the decompressed size of a single ZIP entry
+pub const DEFAULT_MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+// Open a ZIP archive from disk
+pub fn open_zip_archive(archive_path: &Path) -> Result<ZipArchive<File>> {
+    let file = File::open(archive_path)?;
+    Ok(ZipArchive::new(file)?)
+}
+
+// Reject archives with unsafe entry names or entries that declare a
+// decompressed size over the limit
+pub fn check_zip_entries<R: Read + Seek>(archive: &mut ZipArchive<R>, max_entry_bytes: u64) -> Result<()> {
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        sanitize_relative_path(entry.name())?;
+        if entry.size() > max_entry_bytes {
+            return Err(Error::EntryTooLarge {
+                name: entry.name().to_string(),
+                limit: max_entry_bytes,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Decompress a ZIP entry, never reading more than the limit allows even if
+// its header under-reports the size
+pub fn read_zip_entry(entry: &mut impl Read, name: &str, max_entry_bytes: u64) -> Result<Vec<u8>> {
+    let mut file_data = Vec::new();
+    entry.take(max_entry_bytes + 1).read_to_end(&mut file_data)?;
+
+    if file_data.len() as u64 > max_entry_bytes {
+        return Err(Error::EntryTooLarge {
+            name: name.to_string(),
+            limit: max_entry_bytes,
+        });
+    }
+    Ok(file_data)
+}
+
+// Decompress the Excel entries of a ZIP archive one at a time, in archive
+// order, and hand each to `visit` along with its normalized name. Only one
+// entry is held in memory at once, so the archive as a whole stays within
+// the per-entry limit; the first error stops the walk.
+pub fn for_each_zip_workbook(
+    archive_path: &Path,
+    max_entry_bytes: u64,
+    mut visit: impl FnMut(String, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    let mut archive = open_zip_archive(archive_path)?;
+    check_zip_entries(&mut archive, max_entry_bytes)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = sanitize_relative_path(entry.name())?.to_string_lossy().into_owned();
+        if is_excel_file(&name) {
+            let file_data = read_zip_entry(&mut entry, &name, max_entry_bytes)?;
+            visit(name, file_data)?;
+        }
+    }
+    Ok(())
+}
+
+// Whether a file name has a workbook extension we can read
+pub fn is_excel_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".xlsx") || name.ends_with(".xls")
+}
+
+// Zip files into a single archive. Files that share a name are stored as
+// `name_2.xlsx` and so on.
+pub fn zip_files(file_paths: &[String]) -> Result<Vec<u8>> {
+    let mut zip_buffer = Vec::new();
+    let mut zip_writer = ZipWriter::new(Cursor::new(&mut zip_buffer));
+    let mut used_names = HashSet::new();
+
+    for file_path in file_paths {
+        let file_name = Path::new(file_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Invalid(format!("Invalid file name: {}", file_path)))?;
+        let file_name = unique_entry_name(&sanitize_file_name(file_name)?, &mut used_names);
+        zip_writer.start_file::<_, ()>(file_name, zip::write::FileOptions::default())?;
+        let mut file = File::open(file_path)?;
+        std::io::copy(&mut file, &mut zip_writer)?;
+    }
+
+    zip_writer.finish()?;
+    Ok(zip_buffer)
+}
+
+fn unique_entry_name(file_name: &str, used_names: &mut HashSet<String>) -> String {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (file_name, String::new()),
+    };
+    let mut name = file_name.to_string();
+    let mut n = 1;
+    while !used_names.insert(name.clone()) {
+        n += 1;
+        name = format!("{}_{}{}", stem, n, extension);
+    }
+    name
+}
@@ -0,0 +1,131 @@
+use crate::auth::User;
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Upload,
+    Replace,
+    Delete,
+    Merge,
+    Join,
+}
+
+// One line of the audit log
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    // RFC 3339 timestamp
+    pub time: String,
+    pub user: String,
+    pub workspace: String,
+    pub action: AuditAction,
+    // Ids the files had at the time; ids are reassigned when the server
+    // restarts, so `files` is what identifies them later
+    pub file_ids: Vec<usize>,
+    pub files: Vec<String>,
+    // Name of the uploaded file; for merges and joins, every input joined with ", "
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cells_changed: Option<usize>,
+}
+
+impl AuditEntry {
+    pub fn new(user: &User, action: AuditAction, file_ids: Vec<usize>, files: Vec<String>) -> Self {
+        AuditEntry {
+            time: Local::now().to_rfc3339(),
+            user: user.name.clone(),
+            workspace: user.workspace.clone(),
+            action,
+            file_ids,
+            files,
+            upload: None,
+            search: None,
+            replace: None,
+            cells_changed: None,
+        }
+    }
+
+    fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.time).ok()
+    }
+}
+
+// Append-only log of uploads, replaces and deletes, one JSON object per line
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog {
+            path: PathBuf::from(path),
+            file: Mutex::new(file),
+        })
+    }
+
+    // Failing to audit never fails the request itself; the error is logged
+    pub fn record(&self, entry: AuditEntry) {
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to encode audit entry");
+                return;
+            }
+        };
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            tracing::error!(path = %self.path.display(), error = %e, "Failed to write audit log");
+        }
+    }
+
+    // Entries of a workspace, optionally only those at or after `since` and
+    // those touching a file of the given name
+    pub fn query(
+        &self,
+        workspace: &str,
+        since: Option<DateTime<FixedOffset>>,
+        file: Option<&str>,
+    ) -> io::Result<Vec<AuditEntry>> {
+        // Every file of a workspace lives in its output directory, so the
+        // file name alone identifies it
+        let file_name = file.map(|file| Path::new(file).file_name());
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            // Skip lines that were cut short, e.g. by a crash mid-write
+            let entry: AuditEntry = match serde_json::from_str(&line?) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            if entry.workspace != workspace {
+                continue;
+            }
+            if let Some(since) = since {
+                if entry.timestamp().is_none_or(|time| time < since) {
+                    continue;
+                }
+            }
+            if let Some(file_name) = file_name {
+                if !entry.files.iter().any(|file| Path::new(file).file_name() == file_name) {
+                    continue;
+                }
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::{AuthConfig, UserConfig};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use base64::Engine;
+use serde_json::json;
+
+// Routes reachable without credentials, for probes from the orchestrator
+const PUBLIC_PATHS: &[&str] = &["/healthz", "/readyz"];
+
+// Caller identity attached to every request by `require_auth`
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: String,
+    pub workspace: String,
+}
+
+impl User {
+    // Identity used for every request when authentication is disabled
+    fn anonymous() -> Self {
+        User {
+            name: "anonymous".to_string(),
+            workspace: String::new(),
+        }
+    }
+}
+
+impl From<&UserConfig> for User {
+    fn from(user: &UserConfig) -> Self {
+        User {
+            name: user.name.clone(),
+            workspace: user.workspace().to_string(),
+        }
+    }
+}
+
+// Checks `Authorization` headers against the configured users. With no users
+// configured, authentication is disabled and everyone shares one workspace.
+pub struct Authenticator {
+    users: Vec<UserConfig>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Self {
+        Authenticator {
+            users: config.users.clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.users.is_empty()
+    }
+
+    // Accepts `Bearer <token>` or `Basic <base64 name:password>`
+    fn authenticate(&self, header: &str) -> Option<User> {
+        if let Some(token) = header.strip_prefix("Bearer ") {
+            return self
+                .users
+                .iter()
+                .find(|user| user.token.as_deref().is_some_and(|t| secure_eq(t, token.trim())))
+                .map(User::from);
+        }
+
+        let encoded = header.strip_prefix("Basic ")?;
+        let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+        let credentials = String::from_utf8(decoded).ok()?;
+        let (name, password) = credentials.split_once(':')?;
+        self.users
+            .iter()
+            .find(|user| user.name == name && user.password.as_deref().is_some_and(|p| secure_eq(p, password)))
+            .map(User::from)
+    }
+}
+
+// Compare secrets without returning early on the first differing byte
+fn secure_eq(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Middleware rejecting unauthenticated requests with 401 and recording the
+// caller as a `User` request extension for the handlers
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let authenticator = req.app_data::<actix_web::web::Data<Authenticator>>().cloned();
+    let user = match authenticator {
+        _ if PUBLIC_PATHS.contains(&req.path()) => Some(User::anonymous()),
+        Some(authenticator) if authenticator.is_enabled() => req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|header| authenticator.authenticate(header)),
+        _ => Some(User::anonymous()),
+    };
+
+    match user {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        None => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, "Basic realm=\"excel-processor\""))
+                .json(json!({ "message": "Authentication required" }));
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::error::{Error, Result};
+use crate::table::Table;
+use calamine::{Data, ExcelDateTime, ExcelDateTimeType};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::str::FromStr;
+
+// Currency symbols dropped when reading numbers from text
+const CURRENCY_SYMBOLS: &[char] = &['$', '€', '£', '¥', '₹', '₽', '₩', '¢'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextCase {
+    Upper,
+    Lower,
+    // First letter of every word upper case, the rest lower case
+    Title,
+}
+
+impl FromStr for TextCase {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "upper" => Ok(TextCase::Upper),
+            "lower" => Ok(TextCase::Lower),
+            "title" => Ok(TextCase::Title),
+            other => Err(format!("Unknown case {} (expected upper, lower or title)", other)),
+        }
+    }
+}
+
+// Cleaning applied to text cells. Text steps run in field order; a cell left
+// empty becomes a blank cell.
+#[derive(Clone, Debug, Default)]
+pub struct Clean {
+    // Columns to clean by header name or letter; empty cleans every column
+    pub columns: Vec<String>,
+    // Drop control and zero-width characters; tabs and line breaks become spaces
+    pub strip_non_printable: bool,
+    // Replace runs of whitespace with a single space
+    pub collapse_whitespace: bool,
+    pub trim: bool,
+    pub case: Option<TextCase>,
+    // Read text such as "$1,234.50" or "(12)" as a number
+    pub numbers: bool,
+    // chrono formats tried in order to read text as a date, e.g. "%d/%m/%Y"
+    pub date_formats: Vec<String>,
+}
+
+impl Clean {
+    pub fn is_empty(&self) -> bool {
+        !self.strip_non_printable
+            && !self.collapse_whitespace
+            && !self.trim
+            && self.case.is_none()
+            && !self.numbers
+            && self.date_formats.is_empty()
+    }
+}
+
+// Parse a list of cleaning steps such as "trim,collapse,title,numbers". The
+// steps are trim, collapse, strip (non-printable characters), upper, lower,
+// title and numbers.
+pub fn parse_clean(list: &str) -> Result<Clean> {
+    let mut clean = Clean::default();
+    for step in list.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+        match step {
+            "trim" => clean.trim = true,
+            "collapse" => clean.collapse_whitespace = true,
+            "strip" => clean.strip_non_printable = true,
+            "numbers" => clean.numbers = true,
+            _ => {
+                let case = step.parse::<TextCase>().map_err(|_| {
+                    Error::Invalid(format!(
+                        "Unknown cleaning step {} (expected trim, collapse, strip, upper, lower, title or numbers)",
+                        step
+                    ))
+                })?;
+                if clean.case.is_some_and(|other| other != case) {
+                    return Err(Error::Invalid("Only one of upper, lower and title can be used".to_string()));
+                }
+                clean.case = Some(case);
+            }
+        }
+    }
+    if clean.is_empty() {
+        return Err(Error::Invalid("Cleaning step list is empty".to_string()));
+    }
+    Ok(clean)
+}
+
+// Parse the columns to clean, such as "Name,C"
+pub fn parse_clean_columns(list: &str) -> Result<Vec<String>> {
+    let columns: Vec<String> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+        .collect();
+    if columns.is_empty() {
+        return Err(Error::Invalid("Clean column list is empty".to_string()));
+    }
+    Ok(columns)
+}
+
+// Parse a ";"-separated list of chrono date formats such as "%d/%m/%Y;%b %d, %Y"
+pub fn parse_date_formats(list: &str) -> Result<Vec<String>> {
+    let formats: Vec<String> = list
+        .split(';')
+        .map(str::trim)
+        .filter(|format| !format.is_empty())
+        .map(str::to_string)
+        .collect();
+    if formats.is_empty() {
+        return Err(Error::Invalid("Date format list is empty".to_string()));
+    }
+    if let Some(format) = formats.iter().find(|format| StrftimeItems::new(format).any(|item| item == Item::Error)) {
+        return Err(Error::Invalid(format!("Invalid date format {:?}", format)));
+    }
+    Ok(formats)
+}
+
+// Clean the text cells of a table; other cells are left as they are
+pub fn clean_table(mut table: Table, clean: &Clean) -> Result<Table> {
+    let columns: Vec<usize> = if clean.columns.is_empty() {
+        (0..table.header.len()).collect()
+    } else {
+        clean
+            .columns
+            .iter()
+            .map(|column| table.require_column(column))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    for row in &mut table.rows {
+        for &col in &columns {
+            if let Data::String(text) = &row[col] {
+                row[col] = clean_text(text, clean);
+            }
+        }
+    }
+    Ok(table)
+}
+
+fn clean_text(text: &str, clean: &Clean) -> Data {
+    let mut text = text.to_string();
+    if clean.strip_non_printable {
+        text = text
+            .chars()
+            .filter_map(|c| match c {
+                '\t' | '\n' | '\r' => Some(' '),
+                '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{AD}' => None,
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+    }
+    if clean.collapse_whitespace {
+        text = collapse_whitespace(&text);
+    }
+    if clean.trim {
+        text = text.trim().to_string();
+    }
+    match clean.case {
+        Some(TextCase::Upper) => text = text.to_uppercase(),
+        Some(TextCase::Lower) => text = text.to_lowercase(),
+        Some(TextCase::Title) => text = title_case(&text),
+        None => {}
+    }
+
+    // Dates first, so that a format such as "%Y%m%d" wins over numbers
+    if let Some(datetime) = parse_date(text.trim(), &clean.date_formats) {
+        return Data::DateTime(ExcelDateTime::new(excel_serial(datetime), ExcelDateTimeType::DateTime, false));
+    }
+    if clean.numbers {
+        if let Some(number) = parse_number(&text) {
+            return Data::Float(number);
+        }
+    }
+    if text.is_empty() {
+        return Data::Empty;
+    }
+    Data::String(text)
+}
+
+// Keeps a single leading or trailing space, like every other run
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+fn title_case(text: &str) -> String {
+    let mut titled = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if word_start {
+            titled.extend(c.to_uppercase());
+        } else {
+            titled.extend(c.to_lowercase());
+        }
+        word_start = !c.is_alphanumeric() && c != '\'';
+    }
+    titled
+}
+
+// Read a number written for people: currency symbols, spaces, thousands
+// separators in groups of three and accounting-style "(12.50)" negatives
+fn parse_number(text: &str) -> Option<f64> {
+    let mut text = text.trim();
+    let negative_parens = text.starts_with('(') && text.ends_with(')');
+    if negative_parens {
+        text = &text[1..text.len() - 1];
+    }
+    let text: String = text
+        .chars()
+        .filter(|c| !CURRENCY_SYMBOLS.contains(c) && !c.is_whitespace())
+        .collect();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(&text)),
+    };
+    if negative && negative_parens {
+        return None;
+    }
+
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+    if whole.contains(',') {
+        let mut groups = whole.split(',');
+        let first = groups.next().unwrap_or_default();
+        if first.is_empty() || first.len() > 3 || groups.any(|group| group.len() != 3) {
+            return None;
+        }
+    }
+    let whole: String = whole.chars().filter(|&c| c != ',').collect();
+    let fraction = fraction.unwrap_or_default();
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let number: f64 = format!("{}.{}", if whole.is_empty() { "0" } else { &whole }, fraction)
+        .trim_end_matches('.')
+        .parse()
+        .ok()?;
+    Some(if negative || negative_parens { -number } else { number })
+}
+
+fn parse_date(text: &str, formats: &[String]) -> Option<NaiveDateTime> {
+    if text.is_empty() {
+        return None;
+    }
+    formats.iter().find_map(|format| {
+        NaiveDateTime::parse_from_str(text, format)
+            .ok()
+            .or_else(|| NaiveDate::parse_from_str(text, format).ok().map(|date| date.and_time(Default::default())))
+    })
+}
+
+// Excel's serial day number, counted from 1899-12-30 with the phantom
+// 1900-02-29 that calamine also assumes
+fn excel_serial(datetime: NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap_or_default().and_time(Default::default());
+    let days = (datetime - epoch).num_milliseconds() as f64 / 86_400_000.0;
+    if days < 61.0 {
+        days - 1.0
+    } else {
+        days
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_time(Default::default())
+    }
+
+    fn invalid(result: Result<impl std::fmt::Debug>) -> String {
+        match result {
+            Err(Error::Invalid(message)) => message,
+            other => panic!("expected an invalid input error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_numbers_written_for_people() {
+        assert_eq!(parse_number("1234"), Some(1234.0));
+        assert_eq!(parse_number(" $1,234.50 "), Some(1234.5));
+        assert_eq!(parse_number("€ 1 234"), Some(1234.0));
+        assert_eq!(parse_number("1,234,567"), Some(1_234_567.0));
+        assert_eq!(parse_number("(12.50)"), Some(-12.5));
+        assert_eq!(parse_number("-£3"), Some(-3.0));
+        assert_eq!(parse_number("+7"), Some(7.0));
+        assert_eq!(parse_number(".5"), Some(0.5));
+        assert_eq!(parse_number("5."), Some(5.0));
+    }
+
+    #[test]
+    fn rejects_text_that_only_looks_numeric() {
+        for text in ["", "$", ".", "1,2", "12,34,567", ",123", "1234,567", "1.2.3", "(-5)", "12abc", "1e5", "NaN"] {
+            assert_eq!(parse_number(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn reads_dates_with_the_first_matching_format() {
+        let formats = parse_date_formats("%d/%m/%Y; %m/%d/%Y").unwrap();
+        assert_eq!(parse_date("03/04/2024", &formats), Some(datetime(2024, 4, 3)));
+        assert_eq!(parse_date("12/31/2024", &formats), Some(datetime(2024, 12, 31)));
+        assert_eq!(parse_date("2024-12-31", &formats), None);
+        assert_eq!(parse_date("", &formats), None);
+
+        let formats = vec!["%Y-%m-%d %H:%M".to_string()];
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(13, 45, 0);
+        assert_eq!(parse_date("2024-01-31 13:45", &formats), expected);
+    }
+
+    #[test]
+    fn rejects_bad_date_format_lists() {
+        assert_eq!(invalid(parse_date_formats(" ; ")), "Date format list is empty");
+        assert_eq!(invalid(parse_date_formats("%d/%m/%Y;%Q")), "Invalid date format \"%Q\"");
+    }
+
+    #[test]
+    fn counts_excel_serials_with_the_phantom_leap_day() {
+        assert_eq!(excel_serial(datetime(1900, 1, 1)), 1.0);
+        assert_eq!(excel_serial(datetime(1900, 2, 28)), 59.0);
+        assert_eq!(excel_serial(datetime(1900, 3, 1)), 61.0);
+        assert_eq!(excel_serial(datetime(2024, 1, 31)), 45322.0);
+        let noon = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(excel_serial(noon), 45322.5);
+
+        // calamine reads the serial back as the same date
+        for date in [datetime(1900, 1, 1), datetime(1900, 2, 28), datetime(1900, 3, 1), datetime(2024, 1, 31)] {
+            let serial = ExcelDateTime::new(excel_serial(date), ExcelDateTimeType::DateTime, false);
+            assert_eq!(serial.as_datetime(), Some(date));
+        }
+    }
+
+    #[test]
+    fn cleans_text_in_step_order() {
+        let clean = parse_clean("strip,collapse,trim,title").unwrap();
+        let cleaned = clean_text(" \u{200B}o'brien\tand  mcDONALD-smith ", &clean);
+        assert_eq!(cleaned, Data::String("O'brien And Mcdonald-Smith".to_string()));
+        assert_eq!(clean_text("   ", &clean), Data::Empty);
+    }
+
+    #[test]
+    fn turns_cleaned_text_into_numbers_and_dates() {
+        let mut clean = parse_clean("trim,numbers").unwrap();
+        assert_eq!(clean_text(" (1,000) ", &clean), Data::Float(-1000.0));
+        assert_eq!(clean_text("1,2", &clean), Data::String("1,2".to_string()));
+
+        // A date format wins over numbers
+        clean.date_formats = parse_date_formats("%Y%m%d").unwrap();
+        let Data::DateTime(cell) = clean_text("20240131", &clean) else {
+            panic!("expected a date");
+        };
+        assert_eq!(cell.as_f64(), 45322.0);
+    }
+
+    #[test]
+    fn rejects_bad_step_lists() {
+        assert_eq!(invalid(parse_clean(" , ")), "Cleaning step list is empty");
+        assert_eq!(invalid(parse_clean("upper,title")), "Only one of upper, lower and title can be used");
+        assert!(invalid(parse_clean("trim,squash")).starts_with("Unknown cleaning step squash"));
+        assert!(parse_clean("lower,trim,lower").is_ok());
+        assert_eq!(invalid(parse_clean_columns(",")), "Clean column list is empty");
+    }
+}
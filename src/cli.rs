@@ -0,0 +1,761 @@
+use crate::config::ConfigArgs;
+use clap::{Args, Subcommand, ValueEnum};
+use excel_handler::export::{rows_to_csv, rows_to_json, sheet_to_csv, sheet_to_json};
+use excel_handler::archive::{for_each_zip_workbook, is_excel_file};
+use excel_handler::{
+    diff_workbooks, join_workbooks, merge_workbooks, parse_aggregates, parse_clean, parse_clean_columns,
+    parse_columns, parse_date_formats, parse_dedupe_columns, parse_group_by, parse_sort, process_excel_files,
+    process_zip_archive, read_sheets, replace_in_files, search_files, split_workbook, zip_files, Dedupe,
+    DiffOptions, DuplicateKeys, Filter, GroupBy, JoinKind, JoinOptions, Keep, MergeInput, MergeMode, MergeOptions,
+    NoProgress, ProcessSpec, SheetStatus, SplitBy, Table, DEFAULT_MAX_ENTRY_BYTES,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+// Exit codes shared by every batch subcommand
+pub const EXIT_OK: u8 = 0;
+pub const EXIT_NO_MATCH: u8 = 1;
+pub const EXIT_FAILURE: u8 = 2;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default when no subcommand is given)
+    Serve(ConfigArgs),
+    /// Process workbooks, or ZIPs of workbooks, into the output directory
+    Process(ProcessArgs),
+    /// Search workbooks for cells containing a string (exit code 1 when nothing matches)
+    Search(SearchArgs),
+    /// Replace a string in every sheet of the given workbooks, in place
+    Replace(ReplaceArgs),
+    /// Export a sheet as CSV or JSON
+    Export(ExportArgs),
+    /// Merge workbooks, or ZIPs of workbooks, into one workbook
+    Merge(MergeArgs),
+    /// Split a sheet into several workbooks by column value or row count
+    Split(SplitArgs),
+    /// Join a sheet of one workbook onto another by key column, like VLOOKUP
+    Join(JoinArgs),
+    /// Compare two versions of a workbook (exit code 1 when they differ)
+    Diff(DiffArgs),
+}
+
+#[derive(Args)]
+pub struct ProcessArgs {
+    /// Workbook or ZIP paths; glob patterns such as 'reports/*.xlsx' are expanded
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Directory processed workbooks are written to
+    #[arg(long, default_value = "output_files")]
+    output_dir: String,
+
+    /// Also bundle every processed workbook into this ZIP file
+    #[arg(long)]
+    zip: Option<PathBuf>,
+
+    #[command(flatten)]
+    spec: SpecArgs,
+
+    /// Print results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+// Row and column options shared by process and export
+#[derive(Args)]
+pub struct SpecArgs {
+    /// Clean text cells: trim, collapse, strip, upper, lower, title and numbers, e.g. 'trim,collapse,numbers'
+    #[arg(long)]
+    clean: Option<String>,
+
+    /// Columns to clean, by header or letter; every column by default
+    #[arg(long)]
+    clean_columns: Option<String>,
+
+    /// Read text dates in these chrono formats, separated by ';', e.g. '%d/%m/%Y;%b %d, %Y'
+    #[arg(long)]
+    date_formats: Option<String>,
+
+    /// Keep rows matching an expression, e.g. 'Amount > 1000 and Status = "Open"'
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Drop rows whose values in these columns repeat, e.g. 'Email,Region'; '*' compares whole rows
+    #[arg(long)]
+    dedupe: Option<String>,
+
+    /// Which duplicate to keep
+    #[arg(long, default_value = "first", requires = "dedupe")]
+    dedupe_keep: Keep,
+
+    /// Write removed duplicates to a "Duplicates" sheet (process only)
+    #[arg(long, requires = "dedupe")]
+    duplicates_sheet: bool,
+
+    /// Summarise rows grouped by these columns, e.g. 'Region,Status' (export prints the summary)
+    #[arg(long)]
+    group_by: Option<String>,
+
+    /// Summary columns: sum, count, avg, min, max or distinct of a column, e.g. 'sum(Amount),count(*)'
+    #[arg(long)]
+    aggregate: Option<String>,
+
+    /// Sort rows by columns, each optionally followed by asc or desc, e.g. 'Region,Amount desc'
+    #[arg(long)]
+    sort: Option<String>,
+
+    /// Ignore case when sorting text
+    #[arg(long, requires = "sort")]
+    sort_ignore_case: bool,
+
+    /// Columns to keep, in order, by header or letter; "Old:New" renames, e.g. 'Name,C:Total'
+    #[arg(long)]
+    columns: Option<String>,
+}
+
+impl SpecArgs {
+    fn spec(&self) -> Result<ProcessSpec, String> {
+        let mut spec = ProcessSpec::default();
+        if self.clean.is_some() || self.date_formats.is_some() {
+            let mut clean =
+                self.clean.as_deref().map(parse_clean).transpose().map_err(|e| e.to_string())?.unwrap_or_default();
+            clean.columns = self
+                .clean_columns
+                .as_deref()
+                .map(parse_clean_columns)
+                .transpose()
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            clean.date_formats = self
+                .date_formats
+                .as_deref()
+                .map(parse_date_formats)
+                .transpose()
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            spec.clean = Some(clean);
+        }
+        if let Some(filter) = &self.filter {
+            spec.filter = Some(Filter::parse(filter).map_err(|e| e.to_string())?);
+        }
+        if let Some(columns) = &self.dedupe {
+            spec.dedupe = Some(Dedupe {
+                columns: parse_dedupe_columns(columns).map_err(|e| e.to_string())?,
+                keep: self.dedupe_keep,
+            });
+        }
+        spec.duplicates_sheet = self.duplicates_sheet;
+        if self.group_by.is_some() || self.aggregate.is_some() {
+            spec.group_by = Some(GroupBy {
+                columns: self
+                    .group_by
+                    .as_deref()
+                    .map(parse_group_by)
+                    .transpose()
+                    .map_err(|e| e.to_string())?
+                    .unwrap_or_default(),
+                aggregates: self
+                    .aggregate
+                    .as_deref()
+                    .map(parse_aggregates)
+                    .transpose()
+                    .map_err(|e| e.to_string())?
+                    .unwrap_or_default(),
+            });
+        }
+        if let Some(sort) = &self.sort {
+            spec.sort = parse_sort(sort).map_err(|e| e.to_string())?;
+        }
+        spec.sort_ignore_case = self.sort_ignore_case;
+        if let Some(columns) = &self.columns {
+            spec.columns = parse_columns(columns).map_err(|e| e.to_string())?;
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Text to look for
+    query: String,
+
+    /// Workbook paths or glob patterns
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Print results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct ReplaceArgs {
+    /// Text to look for
+    search: String,
+
+    /// Replacement text
+    replace: String,
+
+    /// Workbook paths or glob patterns
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Print results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Workbook to export
+    input: PathBuf,
+
+    /// Sheet to export (defaults to the first sheet)
+    #[arg(long)]
+    sheet: Option<String>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "csv")]
+    format: ExportFormat,
+
+    #[command(flatten)]
+    spec: SpecArgs,
+
+    /// File to write to (defaults to stdout)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Workbook or ZIP paths or glob patterns, merged in the order given
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Workbook to write
+    #[arg(long, short)]
+    output: PathBuf,
+
+    /// stack: one sheet with every file's rows; tabs: every sheet as its own tab
+    #[arg(long, default_value = "stack")]
+    mode: MergeMode,
+
+    /// Sheet to take from each file (defaults to the first, or all in tabs mode)
+    #[arg(long)]
+    sheet: Option<String>,
+
+    /// Add a "Source File" column naming the file each row came from (stack mode)
+    #[arg(long)]
+    source_column: bool,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct SplitArgs {
+    /// Workbook to split
+    input: PathBuf,
+
+    /// Write one workbook per distinct value of this column
+    #[arg(long, required_unless_present = "rows", conflicts_with = "rows")]
+    column: Option<String>,
+
+    /// Write workbooks of at most this many data rows
+    #[arg(long)]
+    rows: Option<usize>,
+
+    /// Sheet to split (defaults to the first sheet)
+    #[arg(long)]
+    sheet: Option<String>,
+
+    /// Directory the pieces are written to
+    #[arg(long, default_value = "output_files")]
+    output_dir: String,
+
+    /// Also bundle the pieces into this ZIP file
+    #[arg(long)]
+    zip: Option<PathBuf>,
+
+    /// Print results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct JoinArgs {
+    /// Workbook whose rows are kept (the left side)
+    left: PathBuf,
+
+    /// Workbook looked up by key (the right side)
+    right: PathBuf,
+
+    /// Workbook to write
+    #[arg(long, short)]
+    output: PathBuf,
+
+    /// Key column on both sides, by header or letter
+    #[arg(long, required_unless_present_all = ["left_key", "right_key"])]
+    key: Option<String>,
+
+    /// Key column of the left workbook (overrides --key)
+    #[arg(long)]
+    left_key: Option<String>,
+
+    /// Key column of the right workbook (overrides --key)
+    #[arg(long)]
+    right_key: Option<String>,
+
+    /// Sheet of the left workbook (defaults to the first)
+    #[arg(long)]
+    left_sheet: Option<String>,
+
+    /// Sheet of the right workbook (defaults to the first)
+    #[arg(long)]
+    right_sheet: Option<String>,
+
+    /// inner: matching rows only; left: every left row; full: every row from both
+    #[arg(long, default_value = "left")]
+    kind: JoinKind,
+
+    /// Right rows sharing a key: all joins each one, first uses the first, error refuses
+    #[arg(long, default_value = "all")]
+    duplicates: DuplicateKeys,
+
+    /// Match keys by value: "00123" matches 123 and text ignores case
+    #[arg(long)]
+    loose_keys: bool,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Old version
+    a: PathBuf,
+
+    /// New version
+    b: PathBuf,
+
+    /// Match rows on this column (header or letter) instead of by position
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Compare only this sheet
+    #[arg(long)]
+    sheet: Option<String>,
+
+    /// Also write a copy of the new version with the changes highlighted
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Print the changes as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+// Run a batch subcommand and map its outcome to an exit code
+pub fn run(command: Command) -> ExitCode {
+    let result = match command {
+        Command::Serve(_) => unreachable!("serve is handled by main"),
+        Command::Process(args) => process(args),
+        Command::Search(args) => search(args),
+        Command::Replace(args) => replace(args),
+        Command::Export(args) => export(args),
+        Command::Merge(args) => merge(args),
+        Command::Split(args) => split(args),
+        Command::Join(args) => join(args),
+        Command::Diff(args) => diff(args),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProcessedInput {
+    input: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicates_removed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn process(args: ProcessArgs) -> Result<u8, String> {
+    let inputs = expand_inputs(&args.inputs)?;
+    let spec = args.spec.spec()?;
+
+    let results: Vec<ProcessedInput> = inputs
+        .iter()
+        .map(|input| {
+            let outcome = match extension_of(input).as_str() {
+                "zip" => process_zip_archive(input, &args.output_dir, DEFAULT_MAX_ENTRY_BYTES, &spec, &NoProgress)
+                    .map(|processed| {
+                        let duplicates_removed =
+                            spec.dedupe.as_ref().map(|_| processed.iter().map(|p| p.duplicates_removed).sum());
+                        (processed.into_iter().map(|p| p.output_file).collect(), duplicates_removed)
+                    })
+                    .map_err(|e| e.to_string()),
+                // Outputs are named after their input, so a batch never collides
+                "xlsx" | "xls" => fs::read(input)
+                    .map_err(|e| format!("Failed to read file: {}", e))
+                    .and_then(|data| {
+                        let stem = input.file_stem().and_then(|stem| stem.to_str());
+                        process_excel_files(&data, &args.output_dir, stem, &spec).map_err(|e| e.to_string())
+                    })
+                    .map(|processed| {
+                        let duplicates_removed = spec.dedupe.as_ref().map(|_| processed.duplicates_removed);
+                        (vec![processed.output_file], duplicates_removed)
+                    }),
+                other => Err(format!("Unsupported file type: {}", other)),
+            };
+
+            match outcome {
+                Ok((outputs, duplicates_removed)) => ProcessedInput {
+                    input: input.display().to_string(),
+                    outputs,
+                    duplicates_removed,
+                    error: None,
+                },
+                Err(e) => ProcessedInput {
+                    input: input.display().to_string(),
+                    outputs: Vec::new(),
+                    duplicates_removed: None,
+                    error: Some(e),
+                },
+            }
+        })
+        .collect();
+
+    let outputs: Vec<String> = results.iter().flat_map(|r| r.outputs.clone()).collect();
+    if let Some(zip_path) = &args.zip {
+        let zip_buffer = zip_files(&outputs).map_err(|e| format!("Failed to bundle results: {}", e))?;
+        fs::write(zip_path, zip_buffer).map_err(|e| format!("Failed to write {}: {}", zip_path.display(), e))?;
+    }
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    if args.json {
+        print_json(&json!({
+            "results": results,
+            "processed": outputs.len(),
+            "failed": failed,
+            "zip": args.zip,
+        }));
+    } else {
+        for result in &results {
+            match &result.error {
+                Some(e) => eprintln!("{}: {}", result.input, e),
+                None => match result.duplicates_removed {
+                    Some(removed) => println!(
+                        "{} -> {} ({} duplicates removed)",
+                        result.input,
+                        result.outputs.join(", "),
+                        removed
+                    ),
+                    None => println!("{} -> {}", result.input, result.outputs.join(", ")),
+                },
+            }
+        }
+        if let Some(zip_path) = &args.zip {
+            println!("Bundled {} files into {}", outputs.len(), zip_path.display());
+        }
+    }
+
+    Ok(if failed > 0 { EXIT_FAILURE } else { EXIT_OK })
+}
+
+fn search(args: SearchArgs) -> Result<u8, String> {
+    let inputs = expand_inputs(&args.inputs)?;
+
+    let results = search_files(&input_paths(&inputs), &args.query).map_err(|e| e.to_string())?;
+
+    if args.json {
+        print_json(&json!({
+            "data": results,
+            "count": results.len(),
+        }));
+    } else {
+        for result in &results {
+            println!(
+                "{}:{}:{}:{}: {}",
+                result.file, result.sheet_name, result.row, result.col, result.value
+            );
+        }
+    }
+
+    Ok(if results.is_empty() { EXIT_NO_MATCH } else { EXIT_OK })
+}
+
+fn replace(args: ReplaceArgs) -> Result<u8, String> {
+    let inputs = expand_inputs(&args.inputs)?;
+
+    let report = replace_in_files(&input_paths(&inputs), &args.search, &args.replace);
+    let cells_changed: usize = report.updated.iter().map(|replacement| replacement.cells_changed).sum();
+
+    if args.json {
+        print_json(&json!({
+            "updated": report.updated,
+            "count": report.updated.len(),
+            "cells_changed": cells_changed,
+            "failed": report.failed,
+        }));
+    } else {
+        for failure in &report.failed {
+            eprintln!("{}: {}", failure.file, failure.error);
+        }
+        println!("Updated {} files ({} cells)", report.updated.len(), cells_changed);
+    }
+
+    Ok(if report.failed.is_empty() { EXIT_OK } else { EXIT_FAILURE })
+}
+
+fn export(args: ExportArgs) -> Result<u8, String> {
+    let sheets = read_sheets(&args.input).map_err(|e| e.to_string())?;
+    let sheet = match &args.sheet {
+        Some(name) => sheets
+            .into_iter()
+            .find(|sheet| &sheet.name == name)
+            .ok_or_else(|| format!("Sheet not found: {}", name))?,
+        None => sheets.into_iter().next().ok_or("Workbook has no readable sheets")?,
+    };
+
+    let spec = args.spec.spec()?;
+    let output = if spec.is_empty() {
+        match args.format {
+            ExportFormat::Csv => sheet_to_csv(&sheet),
+            ExportFormat::Json => serde_json::to_string_pretty(&sheet_to_json(&sheet)).unwrap_or_default() + "\n",
+        }
+    } else {
+        let reshaped = spec.apply(Table::from_sheet(&sheet)).map_err(|e| e.to_string())?;
+        let rows = reshaped.summary.unwrap_or(reshaped.table).to_rows();
+        let rows = rows.iter().map(Vec::as_slice);
+        match args.format {
+            ExportFormat::Csv => rows_to_csv(rows),
+            ExportFormat::Json => serde_json::to_string_pretty(&rows_to_json(&sheet.name, rows)).unwrap_or_default() + "\n",
+        }
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, output).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+        None => print!("{}", output),
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn merge(args: MergeArgs) -> Result<u8, String> {
+    let inputs = expand_inputs(&args.inputs)?;
+
+    let mut workbooks = Vec::new();
+    for input in &inputs {
+        let name = input.display().to_string();
+        match extension_of(input).as_str() {
+            "zip" => {
+                for_each_zip_workbook(input, DEFAULT_MAX_ENTRY_BYTES, |entry, file_data| {
+                    workbooks.push(MergeInput::from_bytes(entry, file_data)?);
+                    Ok(())
+                })
+                .map_err(|e| e.to_string())?;
+            }
+            _ if is_excel_file(&name) => {
+                let file_data = fs::read(input).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+                workbooks.push(MergeInput::from_bytes(name, file_data).map_err(|e| e.to_string())?);
+            }
+            other => return Err(format!("Unsupported file type: {}", other)),
+        }
+    }
+
+    let options = MergeOptions {
+        mode: args.mode,
+        sheet: args.sheet,
+        source_column: args.source_column,
+    };
+    let merged = merge_workbooks(&workbooks, &options, &args.output.to_string_lossy()).map_err(|e| e.to_string())?;
+
+    if args.json {
+        print_json(&json!(merged));
+    } else {
+        println!(
+            "Merged {} workbooks into {} ({} sheets, {} rows)",
+            workbooks.len(),
+            merged.output_file,
+            merged.sheets,
+            merged.rows_written
+        );
+    }
+    Ok(EXIT_OK)
+}
+
+fn split(args: SplitArgs) -> Result<u8, String> {
+    let by = match (args.column, args.rows) {
+        (Some(column), _) => SplitBy::Column(column),
+        (None, Some(rows)) => SplitBy::Rows(rows),
+        (None, None) => return Err("Give --column or --rows".to_string()),
+    };
+
+    fs::create_dir_all(&args.output_dir).map_err(|e| format!("Failed to create {}: {}", args.output_dir, e))?;
+    let pieces = split_workbook(&args.input.to_string_lossy(), args.sheet.as_deref(), &by, &args.output_dir)
+        .map_err(|e| e.to_string())?;
+
+    if let Some(zip_path) = &args.zip {
+        let zip_buffer = zip_files(&pieces).map_err(|e| format!("Failed to bundle results: {}", e))?;
+        fs::write(zip_path, zip_buffer).map_err(|e| format!("Failed to write {}: {}", zip_path.display(), e))?;
+    }
+
+    if args.json {
+        print_json(&json!({
+            "outputs": pieces,
+            "count": pieces.len(),
+            "zip": args.zip,
+        }));
+    } else {
+        for piece in &pieces {
+            println!("{}", piece);
+        }
+        println!("Split {} into {} files", args.input.display(), pieces.len());
+    }
+    Ok(EXIT_OK)
+}
+
+fn join(args: JoinArgs) -> Result<u8, String> {
+    let (Some(left_key), Some(right_key)) = (args.left_key.or(args.key.clone()), args.right_key.or(args.key)) else {
+        return Err("Give --key, or both --left-key and --right-key".to_string());
+    };
+    let options = JoinOptions {
+        kind: args.kind,
+        left_key,
+        right_key,
+        left_sheet: args.left_sheet,
+        right_sheet: args.right_sheet,
+        duplicates: args.duplicates,
+        loose_keys: args.loose_keys,
+    };
+    let joined = join_workbooks(
+        &args.left.to_string_lossy(),
+        &args.right.to_string_lossy(),
+        &options,
+        &args.output.to_string_lossy(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if args.json {
+        print_json(&json!(joined));
+    } else {
+        println!(
+            "Joined into {} ({} rows; {} matched, {} left only, {} right only)",
+            joined.output_file, joined.rows_written, joined.matched, joined.left_only, joined.right_only
+        );
+    }
+    Ok(EXIT_OK)
+}
+
+fn diff(args: DiffArgs) -> Result<u8, String> {
+    let options = DiffOptions {
+        key: args.key,
+        sheet: args.sheet,
+    };
+    let report = args.report.as_ref().map(|path| path.to_string_lossy().into_owned());
+    let diff = diff_workbooks(&args.a.to_string_lossy(), &args.b.to_string_lossy(), &options, report.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    if args.json {
+        print_json(&json!(diff));
+    } else {
+        for sheet in &diff.sheets {
+            match sheet.status {
+                SheetStatus::Same => println!("{}: unchanged", sheet.sheet),
+                SheetStatus::Added => println!("{}: added ({} rows)", sheet.sheet, sheet.added.len()),
+                SheetStatus::Removed => println!("{}: removed ({} rows)", sheet.sheet, sheet.removed.len()),
+                SheetStatus::Changed => {
+                    println!(
+                        "{}: {} added, {} removed, {} changed rows",
+                        sheet.sheet,
+                        sheet.added.len(),
+                        sheet.removed.len(),
+                        sheet.changed.len()
+                    );
+                    for column in &sheet.added_columns {
+                        println!("  + column {}", column);
+                    }
+                    for column in &sheet.removed_columns {
+                        println!("  - column {}", column);
+                    }
+                    for row in &sheet.removed {
+                        println!("  - row {}: {}", row.row, row.values.join(", "));
+                    }
+                    for row in &sheet.added {
+                        println!("  + row {}: {}", row.row, row.values.join(", "));
+                    }
+                    for change in &sheet.changed {
+                        for cell in &change.cells {
+                            println!("  ~ row {} {}: {:?} -> {:?}", change.row_b, cell.column, cell.old, cell.new);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(report) = &report {
+            println!("Wrote report to {}", report);
+        }
+    }
+
+    Ok(if diff.is_same() { EXIT_OK } else { EXIT_NO_MATCH })
+}
+
+// Expand glob patterns; plain paths are passed through and must exist
+fn expand_inputs(patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if pattern.contains(['*', '?', '[']) {
+            let matches: Vec<PathBuf> = glob::glob(pattern)
+                .map_err(|e| format!("Invalid pattern {}: {}", pattern, e))?
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file())
+                .collect();
+            if matches.is_empty() {
+                return Err(format!("No files match {}", pattern));
+            }
+            inputs.extend(matches);
+        } else if Path::new(pattern).is_file() {
+            inputs.push(PathBuf::from(pattern));
+        } else {
+            return Err(format!("File not found: {}", pattern));
+        }
+    }
+    Ok(inputs)
+}
+
+fn extension_of(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+// Library calls take plain string paths
+fn input_paths(inputs: &[PathBuf]) -> Vec<String> {
+    inputs.iter().map(|input| input.to_string_lossy().to_string()).collect()
+}
+
+fn print_json(value: &serde_json::Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
@@ -0,0 +1,334 @@
+use crate::upload::UploadLimits;
+use clap::{Args, ValueEnum};
+use excel_handler::{sanitize_file_name, RetentionPolicy};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Config file picked up from the working directory when --config is not given
+const DEFAULT_CONFIG_FILE: &str = "excel-processor.toml";
+
+// Server settings, loaded from (in increasing precedence) built-in defaults,
+// a TOML file, EXCEL_* environment variables and command-line flags.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub upload: UploadConfig,
+    pub retention: RetentionConfig,
+    pub logging: LoggingConfig,
+    pub auth: AuthConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    // Number of HTTP worker threads; defaults to one per core
+    pub workers: Option<usize>,
+    // Workbook jobs allowed in flight before requests are rejected with 503
+    pub max_pending_jobs: Option<usize>,
+    pub static_dir: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8000,
+            workers: None,
+            max_pending_jobs: None,
+            static_dir: "./static".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub output_dir: String,
+    // Append-only log of uploads, replaces and deletes
+    pub audit_log: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            output_dir: "output_files".to_string(),
+            audit_log: "audit.log".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    pub max_upload_bytes: u64,
+    pub max_entry_bytes: u64,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        let limits = UploadLimits::default();
+        UploadConfig {
+            max_upload_bytes: limits.max_upload_bytes,
+            max_entry_bytes: limits.max_entry_bytes,
+        }
+    }
+}
+
+impl UploadConfig {
+    pub fn limits(&self) -> UploadLimits {
+        UploadLimits {
+            max_upload_bytes: self.max_upload_bytes,
+            max_entry_bytes: self.max_entry_bytes,
+        }
+    }
+}
+
+// How long processed files are kept in the output directory.
+// Every limit is optional; nothing is removed unless one is set.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub max_age_hours: Option<u64>,
+    pub max_total_bytes: Option<u64>,
+    pub max_files: Option<usize>,
+    pub interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_age_hours: None,
+            max_total_bytes: None,
+            max_files: None,
+            interval_secs: 3600,
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: self.max_age_hours.map(|hours| Duration::from_secs(hours * 3600)),
+            max_total_bytes: self.max_total_bytes,
+            max_files: self.max_files,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    // Level or `RUST_LOG`-style filter, e.g. "info" or "info,excel_handler=debug"
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// API users. Authentication is disabled while the list is empty.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub users: Vec<UserConfig>,
+}
+
+// A user authenticates with a bearer token, basic auth, or either. Users
+// sharing a workspace (e.g. a team) see each other's files.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub name: String,
+    pub token: Option<String>,
+    pub password: Option<String>,
+    // Defaults to the user name
+    pub workspace: Option<String>,
+}
+
+impl UserConfig {
+    pub fn workspace(&self) -> &str {
+        self.workspace.as_deref().unwrap_or(&self.name)
+    }
+}
+
+// Command-line flags; each one can also be set through its EXCEL_* variable
+#[derive(Args, Clone, Debug, Default)]
+pub struct ConfigArgs {
+    /// Path to a TOML config file (defaults to ./excel-processor.toml if present)
+    #[arg(long, env = "EXCEL_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to bind the HTTP server to
+    #[arg(long, env = "EXCEL_HOST")]
+    pub host: Option<String>,
+
+    /// Port to bind the HTTP server to
+    #[arg(long, env = "EXCEL_PORT")]
+    pub port: Option<u16>,
+
+    /// Number of HTTP worker threads
+    #[arg(long, env = "EXCEL_WORKERS")]
+    pub workers: Option<usize>,
+
+    /// Workbook jobs allowed in flight before requests are rejected
+    #[arg(long, env = "EXCEL_MAX_PENDING_JOBS")]
+    pub max_pending_jobs: Option<usize>,
+
+    /// Directory served as the web UI
+    #[arg(long, env = "EXCEL_STATIC_DIR")]
+    pub static_dir: Option<String>,
+
+    /// Directory processed workbooks are written to
+    #[arg(long, env = "EXCEL_OUTPUT_DIR")]
+    pub output_dir: Option<String>,
+
+    /// File the audit log is appended to
+    #[arg(long, env = "EXCEL_AUDIT_LOG")]
+    pub audit_log: Option<String>,
+
+    /// Maximum size of an uploaded file in bytes
+    #[arg(long, env = "EXCEL_MAX_UPLOAD_BYTES")]
+    pub max_upload_bytes: Option<u64>,
+
+    /// Maximum decompressed size of a single ZIP entry in bytes
+    #[arg(long, env = "EXCEL_MAX_ENTRY_BYTES")]
+    pub max_entry_bytes: Option<u64>,
+
+    /// Remove output files older than this many hours
+    #[arg(long, env = "EXCEL_RETENTION_MAX_AGE_HOURS")]
+    pub retention_max_age_hours: Option<u64>,
+
+    /// Keep the output directory under this many bytes
+    #[arg(long, env = "EXCEL_RETENTION_MAX_TOTAL_BYTES")]
+    pub retention_max_total_bytes: Option<u64>,
+
+    /// Keep at most this many output files
+    #[arg(long, env = "EXCEL_RETENTION_MAX_FILES")]
+    pub retention_max_files: Option<usize>,
+
+    /// Seconds between automatic retention sweeps
+    #[arg(long, env = "EXCEL_RETENTION_INTERVAL_SECS")]
+    pub retention_interval_secs: Option<u64>,
+
+    /// Log level (error, warn, info, debug, trace) or a RUST_LOG-style filter
+    #[arg(long, env = "EXCEL_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log output format
+    #[arg(long, value_enum, env = "EXCEL_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl Config {
+    // Build the effective configuration from the config file and overrides
+    pub fn load(args: &ConfigArgs) -> Result<Config, String> {
+        let mut config = match &args.config {
+            Some(path) => Config::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Config::default(),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    // Apply flag and environment overrides on top of the loaded values
+    fn apply(&mut self, args: &ConfigArgs) {
+        if let Some(host) = &args.host {
+            self.server.host = host.clone();
+        }
+        if let Some(port) = args.port {
+            self.server.port = port;
+        }
+        if args.workers.is_some() {
+            self.server.workers = args.workers;
+        }
+        if args.max_pending_jobs.is_some() {
+            self.server.max_pending_jobs = args.max_pending_jobs;
+        }
+        if let Some(static_dir) = &args.static_dir {
+            self.server.static_dir = static_dir.clone();
+        }
+        if let Some(output_dir) = &args.output_dir {
+            self.storage.output_dir = output_dir.clone();
+        }
+        if let Some(audit_log) = &args.audit_log {
+            self.storage.audit_log = audit_log.clone();
+        }
+        if let Some(max_upload_bytes) = args.max_upload_bytes {
+            self.upload.max_upload_bytes = max_upload_bytes;
+        }
+        if let Some(max_entry_bytes) = args.max_entry_bytes {
+            self.upload.max_entry_bytes = max_entry_bytes;
+        }
+        if args.retention_max_age_hours.is_some() {
+            self.retention.max_age_hours = args.retention_max_age_hours;
+        }
+        if args.retention_max_total_bytes.is_some() {
+            self.retention.max_total_bytes = args.retention_max_total_bytes;
+        }
+        if args.retention_max_files.is_some() {
+            self.retention.max_files = args.retention_max_files;
+        }
+        if let Some(interval_secs) = args.retention_interval_secs {
+            self.retention.interval_secs = interval_secs;
+        }
+        if let Some(level) = &args.log_level {
+            self.logging.level = level.clone();
+        }
+        if let Some(format) = args.log_format {
+            self.logging.format = format;
+        }
+    }
+
+    // Catch settings that parse but can't work
+    fn validate(&self) -> Result<(), String> {
+        for user in &self.auth.users {
+            if user.name.is_empty() || user.name.contains(':') {
+                return Err(format!("Invalid user name {:?}", user.name));
+            }
+            if user.token.is_none() && user.password.is_none() {
+                return Err(format!("User {} needs a token or a password", user.name));
+            }
+            sanitize_file_name(user.workspace()).map_err(|e| format!("User {}: {}", user.name, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn bind_address(&self) -> (String, u16) {
+        (self.server.host.clone(), self.server.port)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+}
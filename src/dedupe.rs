@@ -0,0 +1,208 @@
+use crate::error::{Error, Result};
+use crate::table::Table;
+use crate::workbook::cell_to_string;
+use calamine::Data;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Name of the sheet removed duplicates are written to when requested
+pub const DUPLICATES_SHEET: &str = "Duplicates";
+
+// Which row of a set of duplicates survives
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Keep {
+    #[default]
+    First,
+    Last,
+    // Drop every row whose key occurs more than once
+    None,
+}
+
+impl FromStr for Keep {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "first" => Ok(Keep::First),
+            "last" => Ok(Keep::Last),
+            "none" => Ok(Keep::None),
+            other => Err(format!("Unknown keep strategy {} (expected first, last or none)", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dedupe {
+    // Key columns by header name or letter; empty compares whole rows
+    pub columns: Vec<String>,
+    pub keep: Keep,
+}
+
+// Parse a key column list such as "Email,Region"; "*" keys on whole rows
+pub fn parse_dedupe_columns(list: &str) -> Result<Vec<String>> {
+    if list.trim() == "*" {
+        return Ok(Vec::new());
+    }
+    let columns: Vec<String> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+        .collect();
+    if columns.is_empty() {
+        return Err(Error::Invalid("Dedupe column list is empty (use * for whole rows)".to_string()));
+    }
+    Ok(columns)
+}
+
+// Remove duplicate rows, comparing the key columns as trimmed text. Returns
+// the remaining table and the removed rows, both in their original order.
+pub fn dedupe_table(mut table: Table, dedupe: &Dedupe) -> Result<(Table, Vec<Vec<Data>>)> {
+    let columns = if dedupe.columns.is_empty() {
+        (0..table.header.len()).collect()
+    } else {
+        dedupe
+            .columns
+            .iter()
+            .map(|column| table.require_column(column))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let keys: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| columns.iter().map(|&col| cell_to_string(&row[col]).trim().to_string()).collect())
+        .collect();
+
+    // Row index of the first and last occurrence, and how often a key occurs
+    let mut seen: HashMap<&[String], (usize, usize, usize)> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        let entry = seen.entry(key.as_slice()).or_insert((i, i, 0));
+        entry.1 = i;
+        entry.2 += 1;
+    }
+
+    let keep: Vec<bool> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let (first, last, count) = seen[key.as_slice()];
+            match dedupe.keep {
+                Keep::First => i == first,
+                Keep::Last => i == last,
+                Keep::None => count == 1,
+            }
+        })
+        .collect();
+
+    let (kept, removed): (Vec<_>, Vec<_>) = table
+        .rows
+        .drain(..)
+        .zip(keep)
+        .partition(|(_, keep)| *keep);
+    table.rows = kept.into_iter().map(|(row, _)| row).collect();
+    Ok((table, removed.into_iter().map(|(row, _)| row).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Data {
+        Data::String(s.to_string())
+    }
+
+    // Email, Region, Amount
+    fn table() -> Table {
+        let rows = [
+            ("a@x.com", "North", 1),
+            ("b@x.com", "South", 2),
+            ("a@x.com", "South", 3),
+            ("c@x.com", "North", 4),
+            ("a@x.com", "North", 5),
+        ];
+        Table {
+            header: vec!["Email".to_string(), "Region".to_string(), "Amount".to_string()],
+            rows: rows
+                .into_iter()
+                .map(|(email, region, amount)| vec![text(email), text(region), Data::Int(amount)])
+                .collect(),
+        }
+    }
+
+    // The Amount of each row, which tells the rows apart
+    fn amounts(rows: &[Vec<Data>]) -> Vec<i64> {
+        rows.iter().map(|row| if let Data::Int(i) = row[2] { i } else { panic!("no amount") }).collect()
+    }
+
+    fn dedupe(table: Table, columns: &str, keep: Keep) -> (Vec<i64>, Vec<i64>) {
+        let dedupe = Dedupe {
+            columns: parse_dedupe_columns(columns).unwrap(),
+            keep,
+        };
+        let (kept, removed) = dedupe_table(table, &dedupe).unwrap();
+        (amounts(&kept.rows), amounts(&removed))
+    }
+
+    #[test]
+    fn keeps_the_first_last_or_no_occurrence() {
+        assert_eq!(dedupe(table(), "Email", Keep::First), (vec![1, 2, 4], vec![3, 5]));
+        assert_eq!(dedupe(table(), "Email", Keep::Last), (vec![2, 4, 5], vec![1, 3]));
+        assert_eq!(dedupe(table(), "Email", Keep::None), (vec![2, 4], vec![1, 3, 5]));
+    }
+
+    #[test]
+    fn keys_on_several_columns_by_name_or_letter() {
+        assert_eq!(dedupe(table(), "Email, B", Keep::First), (vec![1, 2, 3, 4], vec![5]));
+        assert_eq!(dedupe(table(), "A,Region", Keep::Last), (vec![2, 3, 4, 5], vec![1]));
+    }
+
+    #[test]
+    fn star_compares_whole_rows() {
+        let mut table = table();
+        table.rows.push(table.rows[0].clone());
+        table.rows.push(vec![text("a@x.com"), text("North"), Data::Int(6)]);
+        let (kept, removed) = dedupe(table, " * ", Keep::First);
+        assert_eq!(kept, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(removed, [1]);
+    }
+
+    #[test]
+    fn matches_keys_as_trimmed_text() {
+        let table = Table {
+            header: vec!["Id".to_string(), "Row".to_string()],
+            rows: vec![
+                vec![Data::Int(7), Data::Int(1)],
+                vec![Data::Float(7.0), Data::Int(2)],
+                vec![text(" 7 "), Data::Int(3)],
+                vec![text("x"), Data::Int(4)],
+                vec![text("X"), Data::Int(5)],
+                vec![Data::Empty, Data::Int(6)],
+                vec![text("  "), Data::Int(7)],
+            ],
+        };
+        let dedupe = Dedupe {
+            columns: vec!["Id".to_string()],
+            keep: Keep::First,
+        };
+        let (kept, removed) = dedupe_table(table, &dedupe).unwrap();
+        let rows = |rows: &[Vec<Data>]| rows.iter().map(|row| cell_to_string(&row[1])).collect::<Vec<_>>();
+        // Text case still matters
+        assert_eq!(rows(&kept.rows), ["1", "4", "5", "6"]);
+        assert_eq!(rows(&removed), ["2", "3", "7"]);
+    }
+
+    #[test]
+    fn rejects_unknown_columns_and_empty_lists() {
+        let dedupe = Dedupe {
+            columns: vec!["Phone".to_string()],
+            keep: Keep::First,
+        };
+        assert_eq!(dedupe_table(table(), &dedupe).unwrap_err().to_string(), "Unknown column: Phone");
+        assert!(parse_dedupe_columns(" , ").is_err());
+        assert_eq!("last".parse::<Keep>(), Ok(Keep::Last));
+        assert!("all".parse::<Keep>().is_err());
+    }
+}
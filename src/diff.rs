@@ -0,0 +1,468 @@
+use crate::error::{Error, Result};
+use crate::table::{Table, MAX_ROWS};
+use crate::workbook::{cell_to_string, cell_to_text, read_sheets, write_cell_format, Sheet};
+use calamine::Data;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use xlsxwriter::format::{Format, FormatColor};
+use xlsxwriter::Workbook;
+
+// Header of the report column describing each row
+const CHANGE_COLUMN: &str = "Change";
+
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    // Match rows by this column (header name or letter) rather than position
+    pub key: Option<String>,
+    // Compare only this sheet
+    pub sheet: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SheetStatus {
+    Same,
+    Changed,
+    Added,
+    Removed,
+}
+
+// A row only one workbook has. Rows are numbered as in Excel, header = 1.
+#[derive(Serialize, Clone, Debug)]
+pub struct RowRef {
+    pub row: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RowChange {
+    pub row_a: usize,
+    pub row_b: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub cells: Vec<CellChange>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SheetDiff {
+    pub sheet: String,
+    pub status: SheetStatus,
+    // Whether rows were matched by the key column; sheets where either side
+    // lacks it are matched by position
+    pub keyed: bool,
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+    pub added: Vec<RowRef>,
+    pub removed: Vec<RowRef>,
+    pub changed: Vec<RowChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WorkbookDiff {
+    pub sheets: Vec<SheetDiff>,
+    pub added_rows: usize,
+    pub removed_rows: usize,
+    pub changed_rows: usize,
+    pub changed_cells: usize,
+}
+
+impl WorkbookDiff {
+    pub fn is_same(&self) -> bool {
+        self.sheets.iter().all(|sheet| sheet.status == SheetStatus::Same)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RowStatus {
+    Same,
+    Changed,
+    Added,
+    Removed,
+}
+
+// One row of the xlsx report, laid out on the combined columns
+struct ReportRow {
+    status: RowStatus,
+    note: String,
+    cells: Vec<Data>,
+    changed: Vec<bool>,
+}
+
+struct ReportSheet {
+    name: String,
+    header: Vec<String>,
+    rows: Vec<ReportRow>,
+}
+
+// Compare workbook `a` (the old version) with `b` (the new one) sheet by
+// sheet. Sheets are matched by name and columns by header. Rows are matched
+// by the key column on sheets that have it (repeated keys pair up in order)
+// and by position otherwise; a key no compared sheet has is an error. When
+// `report_file` is set, an xlsx report of `b` is written with added rows
+// green, removed rows red and changed cells yellow.
+pub fn diff_workbooks(
+    a_file: &str,
+    b_file: &str,
+    options: &DiffOptions,
+    report_file: Option<&str>,
+) -> Result<WorkbookDiff> {
+    let started = Instant::now();
+    let a_sheets = read_sheets(a_file)?;
+    let b_sheets = read_sheets(b_file)?;
+    if let Some(name) = &options.sheet {
+        if !a_sheets.iter().chain(&b_sheets).any(|sheet| &sheet.name == name) {
+            return Err(Error::Invalid(format!("Sheet not found: {}", name)));
+        }
+    }
+
+    // Sheets of `b` in order, then those only `a` has
+    let mut names: Vec<&str> = b_sheets.iter().map(|sheet| sheet.name.as_str()).collect();
+    names.extend(
+        a_sheets
+            .iter()
+            .map(|sheet| sheet.name.as_str())
+            .filter(|name| !b_sheets.iter().any(|sheet| sheet.name == *name)),
+    );
+    names.retain(|name| options.sheet.as_deref().is_none_or(|sheet| sheet == *name));
+
+    let find = |sheets: &'_ [Sheet], name: &str| sheets.iter().find(|sheet| sheet.name == name).map(Table::from_sheet);
+    let mut sheets = Vec::new();
+    let mut report = Vec::new();
+    for name in names {
+        let (sheet, report_sheet) =
+            diff_sheet(name, find(&a_sheets, name), find(&b_sheets, name), options.key.as_deref());
+        sheets.push(sheet);
+        report.push(report_sheet);
+    }
+    if let Some(key) = &options.key {
+        let compared = sheets.iter().any(|sheet| matches!(sheet.status, SheetStatus::Same | SheetStatus::Changed));
+        if compared && !sheets.iter().any(|sheet| sheet.keyed) {
+            return Err(Error::Invalid(format!("Unknown column: {}", key)));
+        }
+    }
+
+    let diff = WorkbookDiff {
+        added_rows: sheets.iter().map(|sheet| sheet.added.len()).sum(),
+        removed_rows: sheets.iter().map(|sheet| sheet.removed.len()).sum(),
+        changed_rows: sheets.iter().map(|sheet| sheet.changed.len()).sum(),
+        changed_cells: sheets
+            .iter()
+            .flat_map(|sheet| &sheet.changed)
+            .map(|change| change.cells.len())
+            .sum(),
+        sheets,
+    };
+    if let Some(report_file) = report_file {
+        write_report(report_file, &report)?;
+    }
+
+    tracing::info!(
+        a = a_file,
+        b = b_file,
+        added = diff.added_rows,
+        removed = diff.removed_rows,
+        changed = diff.changed_rows,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Compared workbooks"
+    );
+    Ok(diff)
+}
+
+fn diff_sheet(name: &str, a: Option<Table>, b: Option<Table>, key: Option<&str>) -> (SheetDiff, ReportSheet) {
+    let status = match (&a, &b) {
+        (None, _) => SheetStatus::Added,
+        (_, None) => SheetStatus::Removed,
+        _ => SheetStatus::Same,
+    };
+    let a = a.unwrap_or_default();
+    let b = b.unwrap_or_default();
+
+    // Report columns: those of `b`, then those only `a` has
+    let mut header = b.header.clone();
+    header.extend(a.header.iter().filter(|name| b.column(name).is_none()).cloned());
+    let a_cols: Vec<Option<usize>> = header.iter().map(|name| a.column(name)).collect();
+    let b_cols: Vec<Option<usize>> = header.iter().map(|name| b.column(name)).collect();
+    let layout = |row: &[Data], cols: &[Option<usize>]| -> Vec<Data> {
+        cols.iter().map(|col| col.map_or(Data::Empty, |col| row[col].clone())).collect()
+    };
+
+    let key_cols = match key {
+        Some(key) if status == SheetStatus::Same => a.require_column(key).ok().zip(b.require_column(key).ok()),
+        _ => None,
+    };
+
+    let mut diff = SheetDiff {
+        sheet: name.to_string(),
+        status,
+        keyed: key_cols.is_some(),
+        added_columns: if status == SheetStatus::Same {
+            b.header.iter().filter(|name| a.column(name).is_none()).cloned().collect()
+        } else {
+            Vec::new()
+        },
+        removed_columns: if status == SheetStatus::Same {
+            a.header.iter().filter(|name| b.column(name).is_none()).cloned().collect()
+        } else {
+            Vec::new()
+        },
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: 0,
+    };
+
+    let key_of = |row: &[Data], col: Option<usize>| col.map(|col| cell_to_text(&row[col]).trim().to_string());
+
+    // Pair rows of `a` and `b`; None on one side means added or removed
+    let mut pairs: Vec<(Option<usize>, Option<usize>)> = Vec::new();
+    match key_cols {
+        Some((a_key, b_key)) => {
+            let mut by_key: HashMap<String, VecDeque<usize>> = HashMap::new();
+            for (i, row) in a.rows.iter().enumerate() {
+                by_key.entry(cell_to_string(&row[a_key]).trim().to_string()).or_default().push_back(i);
+            }
+            let mut matched = vec![false; a.rows.len()];
+            for (j, row) in b.rows.iter().enumerate() {
+                let i = by_key
+                    .get_mut(cell_to_string(&row[b_key]).trim())
+                    .and_then(VecDeque::pop_front);
+                if let Some(i) = i {
+                    matched[i] = true;
+                }
+                pairs.push((i, Some(j)));
+            }
+            pairs.extend((0..a.rows.len()).filter(|&i| !matched[i]).map(|i| (Some(i), None)));
+        }
+        None => {
+            let len = a.rows.len().max(b.rows.len());
+            pairs.extend((0..len).map(|i| ((i < a.rows.len()).then_some(i), (i < b.rows.len()).then_some(i))));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (i, j) in pairs {
+        match (i, j) {
+            (Some(i), Some(j)) => {
+                let old = layout(&a.rows[i], &a_cols);
+                let new = layout(&b.rows[j], &b_cols);
+                // Only columns both sheets have can change
+                let changed: Vec<bool> = old
+                    .iter()
+                    .zip(&new)
+                    .enumerate()
+                    .map(|(col, (old, new))| {
+                        a_cols[col].is_some() && b_cols[col].is_some() && cell_to_text(old) != cell_to_text(new)
+                    })
+                    .collect();
+                let cells: Vec<CellChange> = changed
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, changed)| **changed)
+                    .map(|(col, _)| CellChange {
+                        column: header[col].clone(),
+                        old: cell_to_text(&old[col]),
+                        new: cell_to_text(&new[col]),
+                    })
+                    .collect();
+
+                if cells.is_empty() {
+                    diff.unchanged += 1;
+                    rows.push(ReportRow {
+                        status: RowStatus::Same,
+                        note: String::new(),
+                        cells: new,
+                        changed,
+                    });
+                } else {
+                    let note = cells
+                        .iter()
+                        .map(|cell| format!("{} was {:?}", cell.column, cell.old))
+                        .collect::<Vec<_>>()
+                        .join("; ");
+                    diff.changed.push(RowChange {
+                        row_a: i + 2,
+                        row_b: j + 2,
+                        key: key_of(&b.rows[j], key_cols.map(|(_, b_key)| b_key)),
+                        cells,
+                    });
+                    rows.push(ReportRow {
+                        status: RowStatus::Changed,
+                        note: format!("changed: {}", note),
+                        cells: new,
+                        changed,
+                    });
+                }
+            }
+            (None, Some(j)) => {
+                let cells = layout(&b.rows[j], &b_cols);
+                diff.added.push(RowRef {
+                    row: j + 2,
+                    key: key_of(&b.rows[j], key_cols.map(|(_, b_key)| b_key)),
+                    values: b.rows[j].iter().map(cell_to_text).collect(),
+                });
+                rows.push(ReportRow {
+                    status: RowStatus::Added,
+                    note: "added".to_string(),
+                    changed: vec![false; cells.len()],
+                    cells,
+                });
+            }
+            (Some(i), None) => {
+                let cells = layout(&a.rows[i], &a_cols);
+                diff.removed.push(RowRef {
+                    row: i + 2,
+                    key: key_of(&a.rows[i], key_cols.map(|(a_key, _)| a_key)),
+                    values: a.rows[i].iter().map(cell_to_text).collect(),
+                });
+                rows.push(ReportRow {
+                    status: RowStatus::Removed,
+                    note: "removed".to_string(),
+                    changed: vec![false; cells.len()],
+                    cells,
+                });
+            }
+            (None, None) => {}
+        }
+    }
+
+    if diff.status == SheetStatus::Same
+        && (!diff.added.is_empty()
+            || !diff.removed.is_empty()
+            || !diff.changed.is_empty()
+            || !diff.added_columns.is_empty()
+            || !diff.removed_columns.is_empty())
+    {
+        diff.status = SheetStatus::Changed;
+    }
+    let report = ReportSheet {
+        name: name.to_string(),
+        header,
+        rows,
+    };
+    (diff, report)
+}
+
+// Write the report: a "Change" column, then the combined columns
+fn write_report(report_file: &str, sheets: &[ReportSheet]) -> Result<()> {
+    if let Some(sheet) = sheets.iter().find(|sheet| sheet.rows.len() >= MAX_ROWS) {
+        return Err(Error::Invalid(format!(
+            "Sheet {} has more than the {} rows Excel allows",
+            sheet.name, MAX_ROWS
+        )));
+    }
+
+    let mut header_format = Format::new();
+    header_format.set_bold();
+    let mut added_format = Format::new();
+    added_format.set_bg_color(FormatColor::Custom(0xC6EFCE));
+    let mut removed_format = Format::new();
+    removed_format.set_bg_color(FormatColor::Custom(0xFFC7CE)).set_font_strikeout();
+    let mut changed_format = Format::new();
+    changed_format.set_bg_color(FormatColor::Custom(0xFFEB9C));
+
+    let workbook = Workbook::new(report_file)?;
+    for sheet in sheets {
+        let mut worksheet = workbook.add_worksheet(Some(&sheet.name))?;
+        worksheet.write_string(0, 0, CHANGE_COLUMN, Some(&header_format))?;
+        for (col, name) in sheet.header.iter().enumerate() {
+            worksheet.write_string(0, col as u16 + 1, name, Some(&header_format))?;
+        }
+
+        for (i, row) in sheet.rows.iter().enumerate() {
+            let row_idx = i as u32 + 1;
+            let row_format = match row.status {
+                RowStatus::Added => Some(&added_format),
+                RowStatus::Removed => Some(&removed_format),
+                RowStatus::Same | RowStatus::Changed => None,
+            };
+            worksheet.write_string(row_idx, 0, &row.note, row_format)?;
+            for (col, cell) in row.cells.iter().enumerate() {
+                let format = if row.changed[col] { Some(&changed_format) } else { row_format };
+                write_cell_format(&mut worksheet, row_idx, col as u16 + 1, cell, format)?;
+            }
+        }
+    }
+    workbook.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    fn table(header: &[&str], rows: &[&[&str]]) -> Table {
+        Table {
+            header: header.iter().map(|name| name.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|cell| Data::String(cell.to_string())).collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn matches_rows_by_key() {
+        let a = table(&["Id", "Name"], &[&["1", "Ann"], &["2", "Bob"], &["3", "Cy"]]);
+        let b = table(&["Id", "Name"], &[&["3", "Cy"], &["1", "Anne"], &["4", "Dee"]]);
+        let (diff, _) = diff_sheet("Sheet1", Some(a), Some(b), Some("Id"));
+        assert!(diff.keyed);
+        assert_eq!(diff.status, SheetStatus::Changed);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!((diff.changed[0].row_a, diff.changed[0].row_b), (2, 3));
+        assert_eq!(diff.changed[0].cells[0].new, "Anne");
+        assert_eq!(diff.added[0].key.as_deref(), Some("4"));
+        assert_eq!(diff.removed[0].key.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn falls_back_to_position_without_the_key_column() {
+        let a = table(&["Name"], &[&["Ann"], &["Bob"]]);
+        let b = table(&["Name"], &[&["Ann"], &["Rob"], &["Cy"]]);
+        let (diff, _) = diff_sheet("Notes", Some(a), Some(b), Some("Id"));
+        assert!(!diff.keyed);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.changed[0].cells[0].old, "Bob");
+        assert_eq!(diff.added[0].row, 4);
+    }
+
+    #[test]
+    fn reports_added_and_removed_columns() {
+        let a = table(&["Name", "Old"], &[&["Ann", "x"]]);
+        let b = table(&["Name", "New"], &[&["Ann", "y"]]);
+        let (diff, report) = diff_sheet("Sheet1", Some(a), Some(b), None);
+        assert_eq!(diff.status, SheetStatus::Changed);
+        assert_eq!(diff.added_columns, ["New"]);
+        assert_eq!(diff.removed_columns, ["Old"]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(report.header, ["Name", "New", "Old"]);
+    }
+
+    #[test]
+    fn reports_dates_as_text() {
+        let date = |serial| Data::DateTime(ExcelDateTime::new(serial, ExcelDateTimeType::DateTime, false));
+        let mut a = table(&["Id", "Due"], &[&["1", ""], &["2", ""]]);
+        let mut b = table(&["Id", "Due"], &[&["1", ""], &["3", ""]]);
+        a.rows[0][1] = date(45322.0);
+        a.rows[1][1] = date(45323.5);
+        b.rows[0][1] = date(45323.0);
+        b.rows[1][1] = Data::DateTimeIso("2024-02-01T12:00:00".to_string());
+        let (diff, _) = diff_sheet("Sheet1", Some(a), Some(b), Some("Id"));
+        assert_eq!(diff.changed[0].cells[0].old, "2024-01-31 00:00:00");
+        assert_eq!(diff.changed[0].cells[0].new, "2024-02-01 00:00:00");
+        assert_eq!(diff.removed[0].values, ["2", "2024-02-01 12:00:00"]);
+        assert_eq!(diff.added[0].values, ["3", "2024-02-01T12:00:00"]);
+    }
+}
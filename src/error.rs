@@ -0,0 +1,76 @@
+use std::fmt;
+
+// Errors returned by the workbook processing API
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file failed
+    Io(std::io::Error),
+    // calamine could not parse a workbook
+    Read(calamine::Error),
+    // xlsxwriter could not write a workbook
+    Write(xlsxwriter::XlsxError),
+    // A ZIP archive could not be read or written
+    Zip(zip::result::ZipError),
+    // A ZIP entry decompresses to more than the configured limit
+    EntryTooLarge { name: String, limit: u64 },
+    // A file name would escape its directory or contains control characters
+    UnsafePath { name: String, reason: &'static str },
+    // The input is readable but not something we can process
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Read(e) => write!(f, "Failed to open workbook: {}", e),
+            Error::Write(e) => write!(f, "Failed to write workbook: {}", e),
+            Error::Zip(e) => write!(f, "ZIP error: {}", e),
+            Error::EntryTooLarge { name, limit } => write!(
+                f,
+                "ZIP entry {} exceeds the maximum decompressed size of {} bytes",
+                name, limit
+            ),
+            Error::UnsafePath { name, reason } => write!(f, "Invalid file name {:?}: {}", name, reason),
+            Error::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Read(e) => Some(e),
+            Error::Write(e) => Some(e),
+            Error::Zip(e) => Some(e),
+            Error::EntryTooLarge { .. } | Error::UnsafePath { .. } | Error::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<calamine::Error> for Error {
+    fn from(e: calamine::Error) -> Self {
+        Error::Read(e)
+    }
+}
+
+impl From<xlsxwriter::XlsxError> for Error {
+    fn from(e: xlsxwriter::XlsxError) -> Self {
+        Error::Write(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
@@ -0,0 +1,64 @@
+use crate::workbook::{cell_to_text, Sheet};
+use calamine::Data;
+use serde_json::json;
+
+// Render a sheet as CSV, quoting fields where needed
+pub fn sheet_to_csv(sheet: &Sheet) -> String {
+    rows_to_csv(sheet.range.rows())
+}
+
+pub fn rows_to_csv<'a>(rows: impl IntoIterator<Item = &'a [Data]>) -> String {
+    rows.into_iter()
+        .map(|row| row.iter().map(|cell| csv_field(&cell_to_text(cell))).collect::<Vec<_>>().join(",") + "\n")
+        .collect()
+}
+
+// Render a sheet as JSON: `{"sheet": name, "rows": [[cell, ...], ...]}`
+pub fn sheet_to_json(sheet: &Sheet) -> serde_json::Value {
+    rows_to_json(&sheet.name, sheet.range.rows())
+}
+
+pub fn rows_to_json<'a>(name: &str, rows: impl IntoIterator<Item = &'a [Data]>) -> serde_json::Value {
+    let rows: Vec<Vec<serde_json::Value>> = rows
+        .into_iter()
+        .map(|row| row.iter().map(cell_to_json).collect())
+        .collect();
+    json!({ "sheet": name, "rows": rows })
+}
+
+// Quote a CSV field when it contains a separator, quote or newline
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Convert a cell to the closest JSON value
+pub fn cell_to_json(cell: &Data) -> serde_json::Value {
+    match cell {
+        Data::Int(i) => json!(i),
+        Data::Float(f) => json!(f),
+        Data::Bool(b) => json!(b),
+        Data::Empty => serde_json::Value::Null,
+        _ => json!(cell_to_text(cell)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    #[test]
+    fn csv_and_json_render_dates_alike() {
+        let date = Data::DateTime(ExcelDateTime::new(45322.5, ExcelDateTimeType::DateTime, false));
+        let row = [Data::String("a, b".to_string()), date, Data::Float(1.5), Data::Empty];
+        assert_eq!(rows_to_csv([&row[..]]), "\"a, b\",2024-01-31 12:00:00,1.5,\n");
+        assert_eq!(
+            rows_to_json("Sheet1", [&row[..]]),
+            json!({ "sheet": "Sheet1", "rows": [["a, b", "2024-01-31 12:00:00", 1.5, null]] })
+        );
+    }
+}
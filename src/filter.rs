@@ -0,0 +1,527 @@
+use crate::error::{Error, Result};
+use crate::table::Table;
+use crate::workbook::{cell_datetime, cell_number, cell_to_string, DATETIME_FORMATS, DATE_FORMAT};
+use calamine::Data;
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// A row filter such as `Amount > 1000 and Status = "Open"`.
+//
+// Comparisons take a column (a bare header name, a column letter, or
+// `[Header With Spaces]`), an operator (= != < <= > >= ~ !~) and a value: a
+// number, a date (2026-01-01 or 2026-01-01T09:30), a quoted string, true or
+// false, or a /regex/ (/regex/i ignores case) for ~ and !~. They combine with
+// and, or, not and parentheses. A cell that can't be read as the value's type
+// compares as unequal, so `Amount > 5` skips text and blank cells.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    source: String,
+    // Columns referenced by the expression; comparisons refer to them by slot
+    columns: Vec<String>,
+    expr: Expr,
+}
+
+// Deepest nesting of parentheses and `not` accepted, so that untrusted input
+// can't exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug)]
+enum Expr {
+    // Chains of and/or are kept flat, however long they are
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare { slot: usize, op: Op, value: Value },
+    Matches { slot: usize, regex: Regex, negate: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    Text(String),
+    Bool(bool),
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Filter> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+            columns: Vec::new(),
+            end: source.chars().count() + 1,
+        };
+        let expr = parser.or()?;
+        if let Some((at, token)) = parser.tokens.get(parser.pos) {
+            return Err(syntax_error(*at, &format!("unexpected {}", token)));
+        }
+        Ok(Filter {
+            source: source.to_string(),
+            columns: parser.columns,
+            expr,
+        })
+    }
+
+    // Keep the rows of the table that match
+    pub fn apply(&self, mut table: Table) -> Result<Table> {
+        let indices = self
+            .columns
+            .iter()
+            .map(|column| table.require_column(column))
+            .collect::<Result<Vec<_>>>()?;
+        table.rows.retain(|row| self.expr.matches(row, &indices));
+        Ok(table)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Filter> {
+        Filter::parse(source)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Expr {
+    fn matches(&self, row: &[Data], indices: &[usize]) -> bool {
+        match self {
+            Expr::And(terms) => terms.iter().all(|term| term.matches(row, indices)),
+            Expr::Or(terms) => terms.iter().any(|term| term.matches(row, indices)),
+            Expr::Not(inner) => !inner.matches(row, indices),
+            Expr::Compare { slot, op, value } => {
+                let ordering = compare(&row[indices[*slot]], value);
+                match op {
+                    Op::Eq => ordering == Some(Ordering::Equal),
+                    Op::Ne => ordering != Some(Ordering::Equal),
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+            Expr::Matches { slot, regex, negate } => regex.is_match(&cell_to_string(&row[indices[*slot]])) != *negate,
+        }
+    }
+}
+
+// Order a cell against a literal, or None when the cell isn't of that type
+fn compare(cell: &Data, value: &Value) -> Option<Ordering> {
+    match value {
+        Value::Number(n) => cell_number(cell)?.partial_cmp(n),
+        Value::Date(date) => Some(cell_datetime(cell)?.date().cmp(date)),
+        Value::DateTime(datetime) => Some(cell_datetime(cell)?.cmp(datetime)),
+        Value::Text(text) => Some(cell_to_string(cell).trim().cmp(text.as_str())),
+        Value::Bool(b) => match cell {
+            Data::Bool(cell) => Some(cell.cmp(b)),
+            _ => None,
+        },
+    }
+}
+
+fn syntax_error(at: usize, message: &str) -> Error {
+    Error::Invalid(format!("Invalid filter at position {}: {}", at, message))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Column(String),
+    Text(String),
+    Regex(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Column(name) => write!(f, "column [{}]", name),
+            Token::Text(text) => write!(f, "string {:?}", text),
+            Token::Regex(pattern) => write!(f, "regex /{}/", pattern),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+// Split a filter into tokens, each paired with its 1-based character position
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let at = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let token = match c {
+            '(' => {
+                i += 1;
+                Token::Open
+            }
+            ')' => {
+                i += 1;
+                Token::Close
+            }
+            '"' | '\'' | '/' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax_error(at, &format!("missing closing {}", close))),
+                        Some(&ch) if ch == close => break,
+                        // Escaped delimiters; regexes keep their own escapes
+                        Some('\\') if chars.get(i + 1) == Some(&close) => {
+                            text.push(close);
+                            i += 2;
+                        }
+                        Some('\\') if c != '/' && chars.get(i + 1) == Some(&'\\') => {
+                            text.push('\\');
+                            i += 2;
+                        }
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                match c {
+                    '[' => Token::Column(text),
+                    // A trailing i makes the match case-insensitive
+                    '/' if chars.get(i) == Some(&'i') && !chars.get(i + 1).is_some_and(|&ch| is_word_char(ch)) => {
+                        i += 1;
+                        Token::Regex(format!("(?i){}", text))
+                    }
+                    '/' => Token::Regex(text),
+                    _ => Token::Text(text),
+                }
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                let op = ["==", "!=", "<>", "<=", ">=", "!~"]
+                    .into_iter()
+                    .find(|op| *op == pair)
+                    .or_else(|| ["=", "<", ">", "~"].into_iter().find(|op| op.starts_with(c)))
+                    .ok_or_else(|| syntax_error(at, &format!("unexpected '{}'", c)))?;
+                i += op.len();
+                Token::Op(op)
+            }
+            _ if is_word_char(c) || c == '-' => {
+                let start = i;
+                while i < chars.len() && (is_word_char(chars[i]) || matches!(chars[i], '-' | '.' | ':')) {
+                    i += 1;
+                }
+                Token::Word(chars[start..i].iter().collect())
+            }
+            _ => return Err(syntax_error(at, &format!("unexpected '{}'", c))),
+        };
+        tokens.push((at, token));
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Recursive descent over: or := and ("or" and)*, and := not ("and" not)*,
+// not := "not" not | "(" or ")" | comparison
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    // Current nesting of parentheses and `not`
+    depth: usize,
+    columns: Vec<String>,
+    // Position reported for errors at the end of the input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(at, _)| *at)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, Token)> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| syntax_error(self.end, &format!("expected {}", expected)))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut terms = vec![self.and()?];
+        while self.keyword("or") {
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.swap_remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut terms = vec![self.not()?];
+        while self.keyword("and") {
+            terms.push(self.not()?);
+        }
+        Ok(if terms.len() == 1 { terms.swap_remove(0) } else { Expr::And(terms) })
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        let at = self.position();
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.nested(at, Self::not)?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let expr = self.nested(at, Self::or)?;
+            match self.next("')'")? {
+                (_, Token::Close) => return Ok(expr),
+                (at, token) => return Err(syntax_error(at, &format!("expected ')' but found {}", token))),
+            }
+        }
+        self.comparison()
+    }
+
+    // Parse one level deeper, refusing to go past MAX_DEPTH
+    fn nested(&mut self, at: usize, parse: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        if self.depth == MAX_DEPTH {
+            return Err(syntax_error(at, &format!("nested more than {} levels deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let column = match self.next("a column")? {
+            (_, Token::Column(name)) => name,
+            (at, Token::Word(word)) if is_keyword(&word) => {
+                return Err(syntax_error(at, &format!("expected a column but found '{}'", word)))
+            }
+            (_, Token::Word(word)) => word,
+            (at, token) => return Err(syntax_error(at, &format!("expected a column but found {}", token))),
+        };
+        let slot = match self.columns.iter().position(|name| *name == column) {
+            Some(slot) => slot,
+            None => {
+                self.columns.push(column);
+                self.columns.len() - 1
+            }
+        };
+
+        let op = match self.next("an operator")? {
+            (_, Token::Op(op)) => op,
+            (at, token) => return Err(syntax_error(at, &format!("expected an operator but found {}", token))),
+        };
+
+        let at = self.position();
+        let (_, token) = self.next("a value")?;
+        if op == "~" || op == "!~" {
+            let Token::Regex(pattern) = token else {
+                return Err(syntax_error(at, &format!("'{}' needs a /regex/ but found {}", op, token)));
+            };
+            let regex = Regex::new(&pattern).map_err(|e| syntax_error(at, &format!("invalid regex: {}", e)))?;
+            return Ok(Expr::Matches {
+                slot,
+                regex,
+                negate: op == "!~",
+            });
+        }
+
+        let value = match token {
+            Token::Text(text) => Value::Text(text),
+            Token::Word(word) => parse_literal(&word)
+                .ok_or_else(|| syntax_error(at, &format!("expected a value but found '{}' (quote strings)", word)))?,
+            token => return Err(syntax_error(at, &format!("expected a value but found {}", token))),
+        };
+        let op = match op {
+            "=" | "==" => Op::Eq,
+            "!=" | "<>" => Op::Ne,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            _ => Op::Ge,
+        };
+        Ok(Expr::Compare { slot, op, value })
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    ["and", "or", "not"].iter().any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
+
+// Unquoted literal: true, false, a date, a date and time, or a number
+fn parse_literal(word: &str) -> Option<Value> {
+    if word.eq_ignore_ascii_case("true") || word.eq_ignore_ascii_case("false") {
+        return Some(Value::Bool(word.eq_ignore_ascii_case("true")));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(word, DATE_FORMAT) {
+        return Some(Value::Date(date));
+    }
+    if let Some(datetime) = DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(word, format).ok())
+    {
+        return Some(Value::DateTime(datetime));
+    }
+    word.parse().ok().filter(|n: &f64| n.is_finite()).map(Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table {
+            header: vec!["Name".to_string(), "Amount".to_string(), "Due Date".to_string(), "Paid".to_string()],
+            rows: vec![
+                vec![
+                    Data::String("Alice".into()),
+                    Data::Float(1500.0),
+                    Data::String("2026-01-15".into()),
+                    Data::Bool(true),
+                ],
+                vec![Data::String("bob".into()), Data::Int(20), Data::String("2026-03-01".into()), Data::Bool(false)],
+                vec![Data::String("Carol".into()), Data::String("n/a".into()), Data::Empty, Data::Bool(false)],
+            ],
+        }
+    }
+
+    fn names(source: &str) -> Vec<String> {
+        let filtered = Filter::parse(source).unwrap().apply(table()).unwrap();
+        filtered.rows.iter().map(|row| cell_to_string(&row[0])).collect()
+    }
+
+    fn error(source: &str) -> String {
+        Filter::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn compares_numbers_and_skips_other_cells() {
+        assert_eq!(names("Amount > 100"), ["Alice"]);
+        assert_eq!(names("Amount <= 20"), ["bob"]);
+        assert_eq!(names("Amount != 20"), ["Alice", "Carol"]);
+    }
+
+    #[test]
+    fn compares_text_dates_and_booleans() {
+        assert_eq!(names(r#"Name = "bob""#), ["bob"]);
+        assert_eq!(names("[Due Date] < 2026-02-01"), ["Alice"]);
+        assert_eq!(names("[Due Date] >= 2026-01-15T12:00"), ["bob"]);
+        assert_eq!(names("Paid = true"), ["Alice"]);
+    }
+
+    #[test]
+    fn resolves_column_letters() {
+        assert_eq!(names("B > 100"), ["Alice"]);
+    }
+
+    #[test]
+    fn matches_regexes() {
+        assert_eq!(names("Name ~ /^[a-c]/"), ["bob"]);
+        assert_eq!(names("Name ~ /^[a-c]/i"), ["Alice", "bob", "Carol"]);
+        assert_eq!(names("Name !~ /o/"), ["Alice"]);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(names(r#"Name = "Carol" or Amount > 10 and Paid = false"#), ["bob", "Carol"]);
+        assert_eq!(names(r#"(Name = "Carol" or Amount > 10) and Paid = false"#), ["bob", "Carol"]);
+        assert_eq!(names(r#"not (Name = "Carol" or Paid = true)"#), ["bob"]);
+        assert_eq!(names("NOT Paid = true AND Amount < 100"), ["bob"]);
+    }
+
+    #[test]
+    fn long_chains_stay_flat() {
+        let source = vec![r#"Name = "x""#; 10_000].join(" or ") + r#" or Name = "bob""#;
+        assert_eq!(names(&source), ["bob"]);
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_positions() {
+        assert_eq!(error(r#"Name = "bob"#), "Invalid filter at position 8: missing closing \"");
+        assert_eq!(error("Amount >"), "Invalid filter at position 9: expected a value");
+        assert_eq!(error("Amount 5"), "Invalid filter at position 8: expected an operator but found '5'");
+        assert_eq!(error("and = 1"), "Invalid filter at position 1: expected a column but found 'and'");
+        assert_eq!(
+            error("Name = bob"),
+            "Invalid filter at position 8: expected a value but found 'bob' (quote strings)"
+        );
+        assert_eq!(
+            error("Name ~ \"bob\""),
+            "Invalid filter at position 8: '~' needs a /regex/ but found string \"bob\""
+        );
+        assert_eq!(error("(Amount > 1"), "Invalid filter at position 12: expected ')'");
+        assert_eq!(error("Amount > 1)"), "Invalid filter at position 11: unexpected ')'");
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        assert!(Filter::parse("Amount > inf").is_err());
+        assert!(Filter::parse("Amount = NaN").is_err());
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let ok = "(".repeat(MAX_DEPTH) + "Amount > 1" + &")".repeat(MAX_DEPTH);
+        assert!(Filter::parse(&ok).is_ok());
+        let too_deep = "(".repeat(MAX_DEPTH + 1) + "Amount > 1" + &")".repeat(MAX_DEPTH + 1);
+        assert!(error(&too_deep).contains("nested more than 64 levels deep"));
+
+        // Far past the limit, this must fail cleanly rather than overflow the stack
+        assert!(Filter::parse(&"(".repeat(100_000)).is_err());
+        assert!(Filter::parse(&("not ".repeat(100_000) + "Amount > 1")).is_err());
+    }
+
+    #[test]
+    fn unknown_columns_fail_when_applied() {
+        let filter = Filter::parse("Missing = 1").unwrap();
+        assert_eq!(filter.apply(table()).unwrap_err().to_string(), "Unknown column: Missing");
+    }
+}
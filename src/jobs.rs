@@ -0,0 +1,380 @@
+use excel_handler::Progress;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+// Upper bound on jobs that are queued or running at the same time
+pub const MAX_ACTIVE_JOBS: usize = 64;
+
+// How long a finished job stays queryable before it is forgotten
+pub const FINISHED_JOB_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Events buffered per job for slow `/jobs/{id}/events` subscribers
+const EVENT_BUFFER: usize = 256;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Skipped,
+}
+
+// Progress of a single uploaded workbook (or ZIP entry) within a job
+#[derive(Serialize, Clone)]
+pub struct FileProgress {
+    pub name: String,
+    pub state: FileState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Snapshot of a job as reported by `/jobs/{id}`
+#[derive(Serialize, Clone)]
+pub struct JobStatus {
+    pub id: usize,
+    pub state: JobState,
+    pub upload: String,
+    pub processed: usize,
+    pub total: usize,
+    pub rows_written: usize,
+    pub files: Vec<FileProgress>,
+    pub download: Option<String>,
+    pub error: Option<String>,
+    // Rows dropped by duplicate removal, when it was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates_removed: Option<usize>,
+    // When the job reached a final state
+    #[serde(skip)]
+    pub finished_at: Option<Instant>,
+    // ZIP bundle of the results on disk, deleted when the job is forgotten
+    #[serde(skip)]
+    pub bundle: Option<String>,
+}
+
+// Progress notifications streamed by `/jobs/{id}/events`
+#[derive(Serialize, Clone)]
+#[serde(untagged)]
+pub enum JobEvent {
+    State {
+        state: JobState,
+    },
+    Entry {
+        index: usize,
+        name: String,
+        state: FileState,
+        processed: usize,
+        total: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Sheet {
+        index: usize,
+        sheet: String,
+        rows_written: usize,
+    },
+    Finished {
+        state: JobState,
+        download: Option<String>,
+        error: Option<String>,
+    },
+}
+
+impl JobEvent {
+    // SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::State { .. } => "state",
+            JobEvent::Entry { .. } => "entry",
+            JobEvent::Sheet { .. } => "sheet",
+            JobEvent::Finished { .. } => "finished",
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, JobEvent::Finished { .. })
+    }
+
+    // Final event for a job that has already finished
+    pub fn finished(status: &JobStatus) -> Self {
+        JobEvent::Finished {
+            state: status.state,
+            download: status.download.clone(),
+            error: status.error.clone(),
+        }
+    }
+}
+
+pub struct Job {
+    // Workspace the job was submitted from; other workspaces can't see it
+    owner: String,
+    status: Mutex<JobStatus>,
+    cancelled: AtomicBool,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl Job {
+    fn new(id: usize, upload: String, owner: String) -> Self {
+        Job {
+            owner,
+            status: Mutex::new(JobStatus {
+                id,
+                state: JobState::Queued,
+                upload,
+                processed: 0,
+                total: 0,
+                rows_written: 0,
+                files: Vec::new(),
+                download: None,
+                error: None,
+                duplicates_removed: None,
+                finished_at: None,
+                bundle: None,
+            }),
+            cancelled: AtomicBool::new(false),
+            events: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.status.lock().unwrap().id
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    // Snapshot the job and subscribe to every event that follows it
+    pub fn subscribe(&self) -> (JobStatus, broadcast::Receiver<JobEvent>) {
+        let status = self.status.lock().unwrap();
+        (status.clone(), self.events.subscribe())
+    }
+
+    // Events are sent while the status lock is held so subscribers never miss
+    // or reorder an update relative to their snapshot
+    fn emit(&self, event: JobEvent) {
+        // Sending only fails when nobody is listening
+        let _ = self.events.send(event);
+    }
+
+    // Request cancellation; returns false if the job already finished
+    pub fn cancel(&self) -> bool {
+        let status = self.status.lock().unwrap();
+        if status.state.is_finished() {
+            return false;
+        }
+        self.cancelled.store(true, Ordering::SeqCst);
+        true
+    }
+
+    pub fn start(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Running;
+        self.emit(JobEvent::State { state: status.state });
+    }
+
+    fn update_entry(&self, index: usize, state: FileState, output: Option<String>, error: Option<String>) {
+        let mut status = self.status.lock().unwrap();
+        if state != FileState::Running {
+            status.processed += 1;
+        }
+        let (processed, total) = (status.processed, status.total);
+        if let Some(entry) = status.files.get_mut(index) {
+            entry.state = state;
+            entry.output = output.clone();
+            entry.error = error.clone();
+            self.emit(JobEvent::Entry {
+                index,
+                name: entry.name.clone(),
+                state,
+                processed,
+                total,
+                output,
+                error,
+            });
+        }
+    }
+
+    pub fn set_duplicates_removed(&self, duplicates_removed: usize) {
+        self.status.lock().unwrap().duplicates_removed = Some(duplicates_removed);
+    }
+
+    // Record where the result bundle is written, before writing it, so
+    // retention leaves it alone and pruning removes it
+    pub fn set_bundle(&self, bundle: String) {
+        self.status.lock().unwrap().bundle = Some(bundle);
+    }
+
+    pub fn finish(&self, download: String) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Done;
+        status.download = Some(download);
+        status.finished_at = Some(Instant::now());
+        self.emit(JobEvent::finished(&status));
+    }
+
+    pub fn fail(&self, error: String) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Failed;
+        status.error = Some(error);
+        status.finished_at = Some(Instant::now());
+        self.emit(JobEvent::finished(&status));
+    }
+
+    pub fn mark_cancelled(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Cancelled;
+        status.finished_at = Some(Instant::now());
+        self.emit(JobEvent::finished(&status));
+    }
+}
+
+// Library progress callbacks update the job status and notify subscribers
+impl Progress for Job {
+    fn entries(&self, names: Vec<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.total = names.len();
+        status.files = names
+            .into_iter()
+            .map(|name| FileProgress {
+                name,
+                state: FileState::Pending,
+                output: None,
+                error: None,
+            })
+            .collect();
+    }
+
+    fn entry_started(&self, index: usize) {
+        self.update_entry(index, FileState::Running, None, None);
+    }
+
+    fn sheet_written(&self, index: usize, sheet: &str, rows_written: usize) {
+        let mut status = self.status.lock().unwrap();
+        status.rows_written += rows_written;
+        self.emit(JobEvent::Sheet {
+            index,
+            sheet: sheet.to_string(),
+            rows_written,
+        });
+    }
+
+    fn entry_done(&self, index: usize, output_file: &str) {
+        self.update_entry(index, FileState::Done, Some(output_file.to_string()), None);
+    }
+
+    fn entry_failed(&self, index: usize, error: &str) {
+        self.update_entry(index, FileState::Failed, None, Some(error.to_string()));
+    }
+
+    fn entry_skipped(&self, index: usize) {
+        self.update_entry(index, FileState::Skipped, None, None);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// In-memory registry of background upload jobs. Finished jobs are dropped
+// once they are older than `FINISHED_JOB_TTL`; their result bundles are
+// kept on disk until then.
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<usize, Arc<Job>>>,
+    next_id: Mutex<usize>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        JobRegistry {
+            jobs: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(0),
+        }
+    }
+
+    // Jobs that are queued or running
+    pub fn active_count(&self) -> usize {
+        Self::count_active(&self.jobs.lock().unwrap())
+    }
+
+    fn count_active(jobs: &HashMap<usize, Arc<Job>>) -> usize {
+        jobs.values().filter(|job| !job.status().state.is_finished()).count()
+    }
+
+    // Register a new queued job, or return None when too many jobs are active
+    pub fn create(&self, upload: String, owner: &str) -> Option<Arc<Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if Self::count_active(&jobs) >= MAX_ACTIVE_JOBS {
+            return None;
+        }
+
+        let mut next_id = self.next_id.lock().unwrap();
+        let job = Arc::new(Job::new(*next_id, upload, owner.to_string()));
+        jobs.insert(*next_id, job.clone());
+        *next_id += 1;
+        Some(job)
+    }
+
+    // Forget jobs that finished more than FINISHED_JOB_TTL ago and return
+    // their result bundles, which the caller deletes
+    pub fn prune(&self) -> Vec<String> {
+        let mut bundles = Vec::new();
+        self.jobs.lock().unwrap().retain(|_, job| {
+            let status = job.status();
+            let expired = status
+                .finished_at
+                .is_some_and(|finished_at| finished_at.elapsed() >= FINISHED_JOB_TTL);
+            if expired {
+                bundles.extend(status.bundle);
+            }
+            !expired
+        });
+        bundles
+    }
+
+    // Result bundles of the jobs still in the registry
+    pub fn live_bundles(&self) -> HashSet<PathBuf> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|job| job.status().bundle.map(PathBuf::from))
+            .collect()
+    }
+
+    // Look up a job submitted from the given workspace
+    pub fn get(&self, id: usize, owner: &str) -> Option<Arc<Job>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .filter(|job| job.owner == owner)
+            .cloned()
+    }
+}
@@ -0,0 +1,399 @@
+use crate::error::{Error, Result};
+use crate::table::{write_workbook, Table};
+use crate::workbook::{cell_number, cell_to_string, read_sheets, select_sheet};
+use calamine::Data;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Instant;
+
+// Name of the sheet the joined rows are written to
+const JOINED_SHEET: &str = "Joined";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinKind {
+    // Only rows whose key appears on both sides
+    Inner,
+    // Every left row, with the right columns blank when nothing matches
+    #[default]
+    Left,
+    // Every row from both sides
+    Full,
+}
+
+impl FromStr for JoinKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "full" => Ok(JoinKind::Full),
+            other => Err(format!("Unknown join kind {} (expected inner, left or full)", other)),
+        }
+    }
+}
+
+// What to do when a key occurs on several right rows
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKeys {
+    // Join the left row with every matching right row
+    #[default]
+    All,
+    // Use the first matching right row, as VLOOKUP does
+    First,
+    // Refuse to join
+    Error,
+}
+
+impl FromStr for DuplicateKeys {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "all" => Ok(DuplicateKeys::All),
+            "first" => Ok(DuplicateKeys::First),
+            "error" => Ok(DuplicateKeys::Error),
+            other => Err(format!("Unknown duplicate key handling {} (expected all, first or error)", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JoinOptions {
+    pub kind: JoinKind,
+    // Key columns by header name or letter
+    pub left_key: String,
+    pub right_key: String,
+    // Sheets to join; default to the first sheet of each workbook
+    pub left_sheet: Option<String>,
+    pub right_sheet: Option<String>,
+    pub duplicates: DuplicateKeys,
+    // Match keys by value rather than exact text: numbers compare as numbers
+    // ("00123" matches 123) and text ignores case
+    pub loose_keys: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JoinedWorkbook {
+    pub output_file: String,
+    pub rows_written: usize,
+    // Left rows with at least one match
+    pub matched: usize,
+    pub left_only: usize,
+    pub right_only: usize,
+}
+
+// Join a sheet of `right_file` onto a sheet of `left_file` and write the
+// result to `output_file`. The output has every left column followed by the
+// right columns other than its key; right headers already used on the left
+// get a " (right)" suffix.
+pub fn join_workbooks(
+    left_file: &str,
+    right_file: &str,
+    options: &JoinOptions,
+    output_file: &str,
+) -> Result<JoinedWorkbook> {
+    let started = Instant::now();
+    let left_sheets = read_sheets(left_file)?;
+    let right_sheets = read_sheets(right_file)?;
+    let left = Table::from_sheet(select_sheet(&left_sheets, options.left_sheet.as_deref())?);
+    let right = Table::from_sheet(select_sheet(&right_sheets, options.right_sheet.as_deref())?);
+    let joined = join_tables(&left, &right, options)?;
+
+    let rows_written = write_workbook(output_file, &[(JOINED_SHEET.to_string(), joined.table.to_rows())])?;
+    tracing::info!(
+        output_file,
+        kind = ?options.kind,
+        matched = joined.matched,
+        left_only = joined.left_only,
+        right_only = joined.right_only,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Joined workbooks"
+    );
+    Ok(JoinedWorkbook {
+        output_file: output_file.to_string(),
+        rows_written,
+        matched: joined.matched,
+        left_only: joined.left_only,
+        right_only: joined.right_only,
+    })
+}
+
+// Joined rows with the counts reported in `JoinedWorkbook`
+#[derive(Debug)]
+struct JoinedTable {
+    table: Table,
+    matched: usize,
+    left_only: usize,
+    right_only: usize,
+}
+
+fn join_tables(left: &Table, right: &Table, options: &JoinOptions) -> Result<JoinedTable> {
+    let left_key = left.require_column(&options.left_key)?;
+    let right_key = right.require_column(&options.right_key)?;
+    let right_columns: Vec<usize> = (0..right.header.len()).filter(|&col| col != right_key).collect();
+
+    // Right rows by key, in sheet order; blank keys never match
+    let mut lookup: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, row) in right.rows.iter().enumerate() {
+        if let Some(key) = join_key(&row[right_key], options.loose_keys) {
+            let matches = lookup.entry(key).or_default();
+            if !matches.is_empty() && options.duplicates == DuplicateKeys::Error {
+                return Err(Error::Invalid(format!(
+                    "Key {} appears more than once in the right workbook",
+                    cell_to_string(&row[right_key]).trim()
+                )));
+            }
+            matches.push(i);
+        }
+    }
+
+    let left_names: HashSet<&str> = left.header.iter().map(String::as_str).collect();
+    let header = left
+        .header
+        .iter()
+        .cloned()
+        .chain(right_columns.iter().map(|&col| {
+            let name = &right.header[col];
+            if left_names.contains(name.as_str()) {
+                format!("{} (right)", name)
+            } else {
+                name.clone()
+            }
+        }))
+        .collect();
+    let mut joined = Table {
+        header,
+        rows: Vec::new(),
+    };
+
+    let mut used = vec![false; right.rows.len()];
+    let (mut matched, mut left_only) = (0, 0);
+    for row in &left.rows {
+        let matches = join_key(&row[left_key], options.loose_keys)
+            .and_then(|key| lookup.get(&key))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let matches = match options.duplicates {
+            DuplicateKeys::First => &matches[..matches.len().min(1)],
+            _ => matches,
+        };
+
+        if matches.is_empty() {
+            left_only += 1;
+            if options.kind != JoinKind::Inner {
+                let mut joined_row = row.clone();
+                joined_row.resize(joined.header.len(), Data::Empty);
+                joined.rows.push(joined_row);
+            }
+            continue;
+        }
+
+        matched += 1;
+        for &i in matches {
+            used[i] = true;
+            let right_row = &right.rows[i];
+            joined
+                .rows
+                .push(row.iter().cloned().chain(right_columns.iter().map(|&col| right_row[col].clone())).collect());
+        }
+    }
+
+    // Right rows no left row matched
+    let mut right_only = 0;
+    for (i, right_row) in right.rows.iter().enumerate() {
+        if used[i] || lookup_matched(&lookup, &used, right_row, right_key, options.loose_keys) {
+            continue;
+        }
+        right_only += 1;
+        if options.kind == JoinKind::Full {
+            let mut joined_row = vec![Data::Empty; left.header.len()];
+            joined_row[left_key] = right_row[right_key].clone();
+            joined_row.extend(right_columns.iter().map(|&col| right_row[col].clone()));
+            joined.rows.push(joined_row);
+        }
+    }
+
+    Ok(JoinedTable {
+        table: joined,
+        matched,
+        left_only,
+        right_only,
+    })
+}
+
+// With `duplicates = first`, later right rows sharing a matched key were
+// skipped on purpose rather than left unmatched
+fn lookup_matched(
+    lookup: &HashMap<String, Vec<usize>>,
+    used: &[bool],
+    row: &[Data],
+    key_col: usize,
+    loose: bool,
+) -> bool {
+    join_key(&row[key_col], loose)
+        .and_then(|key| lookup.get(&key))
+        .is_some_and(|rows| rows.iter().any(|&i| used[i]))
+}
+
+// Text a key cell is matched on, or None for a blank key
+fn join_key(cell: &Data, loose: bool) -> Option<String> {
+    let text = cell_to_string(cell).trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    if !loose {
+        return Some(text);
+    }
+    match cell_number(cell) {
+        Some(n) => Some(n.to_string()),
+        None => Some(text.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Data {
+        Data::String(s.to_string())
+    }
+
+    fn id(i: i64) -> Data {
+        Data::Int(i)
+    }
+
+    // Id, Name
+    fn people() -> Table {
+        Table {
+            header: vec!["Id".to_string(), "Name".to_string()],
+            rows: vec![
+                vec![id(1), text("Ann")],
+                vec![id(2), text("Bob")],
+                vec![id(3), text("Cy")],
+                vec![Data::Empty, text("Dee")],
+            ],
+        }
+    }
+
+    // Id, Amount; key 2 appears twice and keys 4 and blank match nobody
+    fn orders() -> Table {
+        Table {
+            header: vec!["Id".to_string(), "Amount".to_string()],
+            rows: vec![
+                vec![id(2), id(10)],
+                vec![id(1), id(20)],
+                vec![id(2), id(30)],
+                vec![id(4), id(40)],
+                vec![Data::Empty, id(50)],
+            ],
+        }
+    }
+
+    fn options(kind: JoinKind, duplicates: DuplicateKeys) -> JoinOptions {
+        JoinOptions {
+            kind,
+            left_key: "Id".to_string(),
+            right_key: "A".to_string(),
+            duplicates,
+            ..Default::default()
+        }
+    }
+
+    fn join(kind: JoinKind, duplicates: DuplicateKeys) -> JoinedTable {
+        join_tables(&people(), &orders(), &options(kind, duplicates)).unwrap()
+    }
+
+    #[test]
+    fn inner_join_keeps_matched_rows() {
+        let joined = join(JoinKind::Inner, DuplicateKeys::All);
+        assert_eq!(joined.table.header, ["Id", "Name", "Amount"]);
+        assert_eq!(
+            joined.table.rows,
+            [
+                vec![id(1), text("Ann"), id(20)],
+                vec![id(2), text("Bob"), id(10)],
+                vec![id(2), text("Bob"), id(30)],
+            ]
+        );
+        assert_eq!((joined.matched, joined.left_only, joined.right_only), (2, 2, 2));
+    }
+
+    #[test]
+    fn left_join_blanks_unmatched_left_rows() {
+        let joined = join(JoinKind::Left, DuplicateKeys::All);
+        assert_eq!(joined.table.rows.len(), 5);
+        assert_eq!(joined.table.rows[3], [id(3), text("Cy"), Data::Empty]);
+        // Blank keys never match, even each other
+        assert_eq!(joined.table.rows[4], [Data::Empty, text("Dee"), Data::Empty]);
+    }
+
+    #[test]
+    fn full_join_appends_unmatched_right_rows() {
+        let joined = join(JoinKind::Full, DuplicateKeys::All);
+        assert_eq!(joined.table.rows.len(), 7);
+        assert_eq!(joined.table.rows[5], [id(4), Data::Empty, id(40)]);
+        assert_eq!(joined.table.rows[6], [Data::Empty, Data::Empty, id(50)]);
+        assert_eq!((joined.matched, joined.left_only, joined.right_only), (2, 2, 2));
+    }
+
+    #[test]
+    fn duplicate_right_keys() {
+        let joined = join(JoinKind::Full, DuplicateKeys::First);
+        assert_eq!(joined.table.rows[1], [id(2), text("Bob"), id(10)]);
+        // The skipped duplicate is not reported as unmatched
+        assert_eq!(joined.table.rows.len(), 6);
+        assert_eq!(joined.right_only, 2);
+
+        let error = join_tables(&people(), &orders(), &options(JoinKind::Inner, DuplicateKeys::Error));
+        assert_eq!(error.unwrap_err().to_string(), "Key 2 appears more than once in the right workbook");
+    }
+
+    #[test]
+    fn suffixes_right_headers_already_on_the_left() {
+        let mut right = people();
+        right.header[0] = "Key".to_string();
+        let options = JoinOptions {
+            right_key: "Key".to_string(),
+            ..options(JoinKind::Inner, DuplicateKeys::All)
+        };
+        let joined = join_tables(&people(), &right, &options).unwrap();
+        assert_eq!(joined.table.header, ["Id", "Name", "Name (right)"]);
+        assert_eq!(joined.table.rows[0], [id(1), text("Ann"), text("Ann")]);
+    }
+
+    #[test]
+    fn loose_keys_match_numbers_and_ignore_case() {
+        let left = Table {
+            header: vec!["Code".to_string()],
+            rows: vec![vec![text("00123")], vec![text("abc")]],
+        };
+        let right = Table {
+            header: vec!["Code".to_string(), "Label".to_string()],
+            rows: vec![vec![id(123), text("number")], vec![text("ABC"), text("letters")]],
+        };
+        let mut options = JoinOptions {
+            kind: JoinKind::Inner,
+            left_key: "Code".to_string(),
+            right_key: "Code".to_string(),
+            ..Default::default()
+        };
+        assert!(join_tables(&left, &right, &options).unwrap().table.rows.is_empty());
+        options.loose_keys = true;
+        let joined = join_tables(&left, &right, &options).unwrap();
+        assert_eq!(joined.table.rows, [vec![text("00123"), text("number")], vec![text("abc"), text("letters")]]);
+    }
+
+    #[test]
+    fn rejects_unknown_key_columns() {
+        let options = JoinOptions {
+            right_key: "Customer".to_string(),
+            ..options(JoinKind::Left, DuplicateKeys::All)
+        };
+        let error = join_tables(&people(), &orders(), &options).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown column: Customer");
+    }
+}
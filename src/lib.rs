@@ -0,0 +1,45 @@
+//! Excel workbook processing: loading, search and replace, export and ZIP
+//! bundling. The HTTP server and the command-line tool are thin layers over
+//! this API, which has no dependency on actix.
+
+pub mod aggregate;
+pub mod archive;
+pub mod clean;
+pub mod dedupe;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod join;
+pub mod merge;
+pub mod paths;
+pub mod process;
+pub mod retention;
+pub mod search;
+pub mod sort;
+pub mod spec;
+pub mod split;
+pub mod table;
+pub mod workbook;
+
+pub use aggregate::{aggregate_table, parse_aggregates, parse_group_by, Aggregate, AggregateFn, GroupBy};
+pub use archive::{zip_files, DEFAULT_MAX_ENTRY_BYTES};
+pub use clean::{clean_table, parse_clean, parse_clean_columns, parse_date_formats, Clean, TextCase};
+pub use dedupe::{dedupe_table, parse_dedupe_columns, Dedupe, Keep};
+pub use diff::{diff_workbooks, DiffOptions, SheetStatus, WorkbookDiff};
+pub use error::{Error, Result};
+pub use filter::Filter;
+pub use join::{join_workbooks, DuplicateKeys, JoinKind, JoinOptions, JoinedWorkbook};
+pub use merge::{merge_workbooks, MergeInput, MergeMode, MergeOptions, MergedWorkbook};
+pub use paths::{output_path, sanitize_file_name, sanitize_relative_path, unique_output_path};
+pub use process::{output_directory, process_excel_files, process_zip_archive, NoProgress, ProcessedWorkbook, Progress};
+pub use retention::{apply_retention, read_pins, write_pins, RetentionPolicy, RetentionReport, PINS_FILE};
+pub use search::{
+    replace_in_files, replace_in_workbook, search_files, search_workbook, ReplaceFailure, ReplaceReport, Replacement,
+    SearchResult,
+};
+pub use sort::{parse_sort, sort_rows, sort_table, SortKey};
+pub use spec::{parse_columns, ColumnSpec, ProcessSpec, Reshaped};
+pub use split::{split_workbook, SplitBy};
+pub use table::Table;
+pub use workbook::{cell_to_string, cell_to_text, read_sheets, Sheet};
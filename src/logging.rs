@@ -0,0 +1,22 @@
+use crate::config::{LogFormat, LoggingConfig};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+// Install the global log subscriber. Logs go to stderr so CLI output on
+// stdout stays machine-readable. `RUST_LOG`, when set, wins over the
+// configured level. Spans are logged when they close, which is what turns
+// the per-request spans into access log lines with timings.
+pub fn init(config: &LoggingConfig) -> Result<(), String> {
+    let directives = std::env::var("RUST_LOG").unwrap_or_else(|_| config.level.clone());
+    let filter = EnvFilter::try_new(&directives).map_err(|e| format!("Invalid log level {}: {}", directives, e))?;
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).try_init(),
+    };
+    result.map_err(|e| format!("Failed to initialize logging: {}", e))
+}
@@ -0,0 +1,224 @@
+use crate::error::{Error, Result};
+use crate::table::{is_blank_row, write_workbook, Table};
+use crate::workbook::{read_sheets_from_bytes, Sheet};
+use calamine::Data;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+
+// Excel's limit on sheet name length
+const MAX_SHEET_NAME_LEN: usize = 31;
+
+// Header of the optional column naming the file each row came from
+const SOURCE_COLUMN: &str = "Source File";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    // Stack the rows of one sheet per file into a single sheet
+    #[default]
+    Stack,
+    // Copy every sheet of every file into one workbook as separate tabs
+    Tabs,
+}
+
+impl FromStr for MergeMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "stack" => Ok(MergeMode::Stack),
+            "tabs" => Ok(MergeMode::Tabs),
+            other => Err(format!("Unknown merge mode {} (expected stack or tabs)", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    pub mode: MergeMode,
+    // Sheet to take from every file; defaults to the first (stack) or all (tabs)
+    pub sheet: Option<String>,
+    // Stack mode only: prepend a column with the source file name
+    pub source_column: bool,
+}
+
+// A workbook to merge, named after the file it came from
+pub struct MergeInput {
+    pub name: String,
+    pub sheets: Vec<Sheet>,
+}
+
+impl MergeInput {
+    pub fn from_bytes(name: String, file_data: Vec<u8>) -> Result<Self> {
+        let sheets = read_sheets_from_bytes(file_data)?;
+        Ok(MergeInput { name, sheets })
+    }
+
+    // The sheets selected by `sheet`, or every sheet when it is None
+    fn select(&self, sheet: Option<&str>) -> Result<Vec<&Sheet>> {
+        match sheet {
+            Some(name) => self
+                .sheets
+                .iter()
+                .find(|sheet| sheet.name == name)
+                .map(|sheet| vec![sheet])
+                .ok_or_else(|| Error::Invalid(format!("Sheet {} not found in {}", name, self.name))),
+            None => Ok(self.sheets.iter().collect()),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MergedWorkbook {
+    pub output_file: String,
+    pub sheets: usize,
+    pub rows_written: usize,
+}
+
+// Merge the inputs into a new workbook at `output_file`
+pub fn merge_workbooks(inputs: &[MergeInput], options: &MergeOptions, output_file: &str) -> Result<MergedWorkbook> {
+    if inputs.is_empty() {
+        return Err(Error::Invalid("No workbooks to merge".to_string()));
+    }
+
+    let started = Instant::now();
+    let sheets = match options.mode {
+        MergeMode::Stack => vec![("Merged".to_string(), stack_inputs(inputs, options)?.to_rows())],
+        MergeMode::Tabs => tab_inputs(inputs, options)?,
+    };
+    let rows_written = write_workbook(output_file, &sheets)?;
+
+    tracing::info!(
+        output_file,
+        inputs = inputs.len(),
+        sheets = sheets.len(),
+        rows_written,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Merged workbooks"
+    );
+    Ok(MergedWorkbook {
+        output_file: output_file.to_string(),
+        sheets: sheets.len(),
+        rows_written,
+    })
+}
+
+// Stack one sheet per input. Columns are matched by header, so files may
+// order their columns differently or add new ones; the merged header lists
+// every column in order of first appearance. Blank headers are named
+// "Column N" after their position.
+fn stack_inputs(inputs: &[MergeInput], options: &MergeOptions) -> Result<Table> {
+    let mut tables = Vec::new();
+    for input in inputs {
+        let sheet = match options.sheet {
+            Some(_) => input.select(options.sheet.as_deref())?[0],
+            None => match input.sheets.first() {
+                Some(sheet) => sheet,
+                None => continue,
+            },
+        };
+        let mut table = Table::from_sheet(sheet);
+        for (col, name) in table.header.iter_mut().enumerate() {
+            if name.is_empty() {
+                *name = format!("Column {}", col + 1);
+            }
+        }
+        tables.push((input, table));
+    }
+
+    // The source column must not take over a column the inputs already have
+    let mut merged = Table::default();
+    if options.source_column {
+        let mut source_column = SOURCE_COLUMN.to_string();
+        let mut n = 1;
+        while tables.iter().any(|(_, table)| table.header.contains(&source_column)) {
+            n += 1;
+            source_column = format!("{} ({})", SOURCE_COLUMN, n);
+        }
+        merged.header.push(source_column);
+    }
+
+    for (input, table) in tables {
+        // A header repeated within one file maps to "Name (2)", "Name (3)", ...
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        let mapping: Vec<usize> = table
+            .header
+            .iter()
+            .map(|name| {
+                let count = seen.entry(name).or_insert(0);
+                *count += 1;
+                let key = if *count > 1 { format!("{} ({})", name, count) } else { name.clone() };
+                merged.column(&key).unwrap_or_else(|| {
+                    merged.header.push(key);
+                    merged.header.len() - 1
+                })
+            })
+            .collect();
+
+        for row in table.rows.iter().filter(|row| !is_blank_row(row)) {
+            let mut merged_row = vec![Data::Empty; merged.header.len()];
+            if options.source_column {
+                merged_row[0] = Data::String(input.name.clone());
+            }
+            for (col, cell) in row.iter().enumerate() {
+                merged_row[mapping[col]] = cell.clone();
+            }
+            merged.rows.push(merged_row);
+        }
+    }
+
+    // Rows stacked before later files added columns are shorter; pad them
+    let width = merged.header.len();
+    for row in &mut merged.rows {
+        row.resize(width, Data::Empty);
+    }
+    Ok(merged)
+}
+
+// One tab per input sheet, named after the file (and the sheet, when a file
+// contributes several)
+fn tab_inputs(inputs: &[MergeInput], options: &MergeOptions) -> Result<Vec<(String, Vec<Vec<Data>>)>> {
+    let mut used = HashSet::new();
+    let mut tabs = Vec::new();
+    for input in inputs {
+        let stem = Path::new(&input.name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| input.name.clone());
+        let sheets = input.select(options.sheet.as_deref())?;
+        let several = sheets.len() > 1;
+
+        for sheet in sheets {
+            let base = if several { format!("{} - {}", stem, sheet.name) } else { stem.clone() };
+            let rows = sheet.range.rows().map(|row| row.to_vec()).collect();
+            tabs.push((unique_sheet_name(&base, &mut used), rows));
+        }
+    }
+    Ok(tabs)
+}
+
+// Make a valid sheet name that no earlier tab uses. Excel compares names
+// case-insensitively, forbids []:*?/\ and caps them at 31 characters.
+fn unique_sheet_name(base: &str, used: &mut HashSet<String>) -> String {
+    let cleaned: String = base
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim_matches('\'').trim();
+    let cleaned = if cleaned.is_empty() { "Sheet" } else { cleaned };
+
+    let mut suffix = String::new();
+    let mut n = 1;
+    loop {
+        let room = MAX_SHEET_NAME_LEN - suffix.chars().count();
+        let name: String = cleaned.chars().take(room).collect::<String>() + &suffix;
+        if used.insert(name.to_lowercase()) {
+            return name;
+        }
+        n += 1;
+        suffix = format!(" ({})", n);
+    }
+}
@@ -0,0 +1,123 @@
+use excel_handler::Progress;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+// Counters exported in Prometheus text format by `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    uploads: IntCounterVec,
+    pub files_processed: IntCounter,
+    pub files_failed: IntCounter,
+    pub bytes_in: IntCounter,
+    pub bytes_out: IntCounter,
+    pub rows_written: IntCounter,
+    // Latency of request handlers that run a workbook operation, labelled by operation
+    pub operation_seconds: HistogramVec,
+    active_jobs: IntGauge,
+    tracked_files: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Metrics {
+            uploads: IntCounterVec::new(Opts::new("excel_uploads_total", "Uploads received, by file type"), &["type"])
+                .unwrap(),
+            files_processed: IntCounter::new("excel_files_processed_total", "Workbooks converted successfully").unwrap(),
+            files_failed: IntCounter::new("excel_files_failed_total", "Workbooks that failed to convert").unwrap(),
+            bytes_in: IntCounter::new("excel_bytes_in_total", "Bytes received in uploads").unwrap(),
+            bytes_out: IntCounter::new("excel_bytes_out_total", "Bytes sent in result bundles").unwrap(),
+            rows_written: IntCounter::new("excel_rows_written_total", "Rows written to output workbooks").unwrap(),
+            operation_seconds: HistogramVec::new(
+                HistogramOpts::new("excel_operation_duration_seconds", "Duration of workbook operations such as search, join or diff"),
+                &["operation"],
+            )
+            .unwrap(),
+            active_jobs: IntGauge::new("excel_active_jobs", "Background jobs queued or running").unwrap(),
+            tracked_files: IntGauge::new("excel_tracked_files", "Files tracked across all workspaces").unwrap(),
+            registry,
+        };
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(metrics.uploads.clone()),
+            Box::new(metrics.files_processed.clone()),
+            Box::new(metrics.files_failed.clone()),
+            Box::new(metrics.bytes_in.clone()),
+            Box::new(metrics.bytes_out.clone()),
+            Box::new(metrics.rows_written.clone()),
+            Box::new(metrics.operation_seconds.clone()),
+            Box::new(metrics.active_jobs.clone()),
+            Box::new(metrics.tracked_files.clone()),
+        ];
+        for collector in collectors {
+            // Names are fixed above, so registration can only fail on a typo
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+
+    pub fn upload_received(&self, file_type: &str, bytes: u64) {
+        let label = match file_type {
+            "zip" | "xlsx" | "xls" => file_type,
+            _ => "other",
+        };
+        self.uploads.with_label_values(&[label]).inc();
+        self.bytes_in.inc_by(bytes);
+    }
+
+    // Render every metric; gauges are sampled by the caller at scrape time
+    pub fn render(&self, active_jobs: usize, tracked_files: usize) -> String {
+        self.active_jobs.set(active_jobs as i64);
+        self.tracked_files.set(tracked_files as i64);
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+// Progress sink that counts processed entries before forwarding to another sink
+pub struct CountingProgress<'a> {
+    metrics: &'a Metrics,
+    inner: &'a dyn Progress,
+}
+
+impl<'a> CountingProgress<'a> {
+    pub fn new(metrics: &'a Metrics, inner: &'a dyn Progress) -> Self {
+        CountingProgress { metrics, inner }
+    }
+}
+
+impl Progress for CountingProgress<'_> {
+    fn entries(&self, names: Vec<String>) {
+        self.inner.entries(names);
+    }
+
+    fn entry_started(&self, index: usize) {
+        self.inner.entry_started(index);
+    }
+
+    fn sheet_written(&self, index: usize, sheet: &str, rows_written: usize) {
+        self.metrics.rows_written.inc_by(rows_written as u64);
+        self.inner.sheet_written(index, sheet, rows_written);
+    }
+
+    fn entry_done(&self, index: usize, output_file: &str) {
+        self.metrics.files_processed.inc();
+        self.inner.entry_done(index, output_file);
+    }
+
+    fn entry_failed(&self, index: usize, error: &str) {
+        self.metrics.files_failed.inc();
+        self.inner.entry_failed(index, error);
+    }
+
+    fn entry_skipped(&self, index: usize) {
+        self.inner.entry_skipped(index);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
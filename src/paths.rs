@@ -0,0 +1,165 @@
+use crate::error::{Error, Result};
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// Normalize an untrusted relative path such as a ZIP entry name. Backslashes
+// are treated as separators and `.` components dropped; `..`, absolute paths
+// (drive letters and UNC paths included) and control characters (including
+// NUL) are rejected.
+pub fn sanitize_relative_path(name: &str) -> Result<PathBuf> {
+    let unsafe_path = |reason| Error::UnsafePath {
+        name: name.to_string(),
+        reason,
+    };
+
+    if name.chars().any(char::is_control) {
+        return Err(unsafe_path("contains control characters"));
+    }
+
+    // A leading separator also catches UNC paths such as `\\server\share`
+    let normalized = name.replace('\\', "/");
+    let has_drive = matches!(normalized.as_bytes(), [drive, b':', ..] if drive.is_ascii_alphabetic());
+    if normalized.starts_with('/') || has_drive {
+        return Err(unsafe_path("is an absolute path"));
+    }
+
+    let mut path = PathBuf::new();
+    for component in normalized.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(unsafe_path("refers to a parent directory")),
+            component => path.push(component),
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_path("is empty"));
+    }
+    Ok(path)
+}
+
+// Validate a bare file name: the same rules as above, and no directories
+pub fn sanitize_file_name(name: &str) -> Result<String> {
+    let path = sanitize_relative_path(name)?;
+    if path.components().count() != 1 {
+        return Err(Error::UnsafePath {
+            name: name.to_string(),
+            reason: "must not contain directories",
+        });
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// Path of a file directly inside the output directory
+pub fn output_path(output_dir: &str, file_name: &str) -> Result<String> {
+    let file_name = sanitize_file_name(file_name)?;
+    Ok(Path::new(output_dir).join(file_name).to_string_lossy().into_owned())
+}
+
+// Claim a new file in the output directory named `{stem}.{extension}`, or
+// `{stem}_2.{extension}` and so on when that is taken. The file is created
+// empty so concurrent callers never get the same path.
+pub fn unique_output_path(output_dir: &str, stem: &str, extension: &str) -> Result<String> {
+    let mut path = output_path(output_dir, &format!("{}.{}", stem, extension))?;
+    let mut n = 1;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                n += 1;
+                path = output_path(output_dir, &format!("{}_{}.{}", stem, n, extension))?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(name: &str) -> &'static str {
+        match sanitize_relative_path(name) {
+            Err(Error::UnsafePath { reason, .. }) => reason,
+            other => panic!("expected {:?} to be rejected, got {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn normalizes_separators_and_dot_components() {
+        assert_eq!(sanitize_relative_path("a/b.xlsx").unwrap(), PathBuf::from("a/b.xlsx"));
+        assert_eq!(sanitize_relative_path("a\\b.xlsx").unwrap(), PathBuf::from("a/b.xlsx"));
+        assert_eq!(sanitize_relative_path("./a//./b.xlsx").unwrap(), PathBuf::from("a/b.xlsx"));
+        assert_eq!(sanitize_relative_path("a/").unwrap(), PathBuf::from("a"));
+        assert_eq!(sanitize_relative_path("..report.xlsx").unwrap(), PathBuf::from("..report.xlsx"));
+    }
+
+    #[test]
+    fn rejects_parent_directories() {
+        for name in ["..", "../a.xlsx", "a/../../b.xlsx", "a\\..\\b.xlsx", "a/.."] {
+            assert_eq!(rejection(name), "refers to a parent directory", "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let names = [
+            "/etc/passwd",
+            "\\windows\\win.ini",
+            "\\\\server\\share\\a.xlsx",
+            "//server/share",
+            "C:\\a.xlsx",
+            "c:a.xlsx",
+            "Z:",
+        ];
+        for name in names {
+            assert_eq!(rejection(name), "is an absolute path", "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn only_letters_make_a_drive_prefix() {
+        assert_eq!(sanitize_file_name("1:2.xlsx").unwrap(), "1:2.xlsx");
+        let error = sanitize_file_name("a:b").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid file name \"a:b\": is an absolute path");
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        for name in ["a\0.xlsx", "a\n.xlsx", "\u{1b}[31m.xlsx", "a\u{7f}.xlsx"] {
+            assert_eq!(rejection(name), "contains control characters", "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        for name in ["", ".", "./", "./."] {
+            assert_eq!(rejection(name), "is empty", "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn file_names_have_no_directories() {
+        assert_eq!(sanitize_file_name("./report.xlsx").unwrap(), "report.xlsx");
+        assert!(matches!(
+            sanitize_file_name("a/report.xlsx"),
+            Err(Error::UnsafePath { reason: "must not contain directories", .. })
+        ));
+        assert!(output_path("out", "../report.xlsx").is_err());
+        assert_eq!(output_path("out", "report.xlsx").unwrap(), Path::new("out").join("report.xlsx").to_string_lossy());
+    }
+
+    #[test]
+    fn unique_output_paths_never_repeat() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_string_lossy();
+        let first = unique_output_path(&dir, "report", "xlsx").unwrap();
+        let second = unique_output_path(&dir, "report", "xlsx").unwrap();
+        let third = unique_output_path(&dir, "report", "xlsx").unwrap();
+        assert!(first.ends_with("/report.xlsx"));
+        assert!(second.ends_with("/report_2.xlsx"));
+        assert!(third.ends_with("/report_3.xlsx"));
+        assert!(Path::new(&third).is_file());
+    }
+}
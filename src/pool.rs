@@ -0,0 +1,82 @@
+use actix_web::{web, Error};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Bounded pool for CPU/IO-bound workbook work.
+//
+// Every job holds a permit for as long as it is queued or running on the
+// blocking thread pool, so at most `capacity` jobs are in flight. Once the
+// pool is saturated new jobs are rejected with 503 instead of piling up.
+pub struct WorkerPool {
+    permits: Arc<Semaphore>,
+    capacity: usize,
+}
+
+impl WorkerPool {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        WorkerPool {
+            permits: Arc::new(Semaphore::new(capacity)),
+            capacity,
+        }
+    }
+
+    // Size the pool from the number of available cores
+    pub fn with_default_capacity() -> Self {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        WorkerPool::new(cores * 4)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Slots free for new jobs right now
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    // Run `job` off the actix executor, or fail with 503 when the pool is full
+    pub async fn run<F, T>(&self, job: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.permits.clone().try_acquire_owned().map_err(|_| {
+            actix_web::error::ErrorServiceUnavailable("Server is busy, please retry later")
+        })?;
+
+        Self::spawn(permit, job).await
+    }
+
+    // Like `run`, but wait for a free slot instead of rejecting the job
+    pub async fn run_when_ready<F, T>(&self, job: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+
+        Self::spawn(permit, job).await
+    }
+
+    async fn spawn<F, T>(permit: OwnedSemaphorePermit, job: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        // Keep the caller's span (and so its request id) on the blocking thread
+        let span = tracing::Span::current();
+        web::block(move || {
+            let _permit = permit;
+            span.in_scope(job)
+        })
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)
+    }
+}
@@ -0,0 +1,256 @@
+use crate::archive::{check_zip_entries, open_zip_archive, read_zip_entry};
+use crate::error::{Error, Result};
+use crate::paths::{sanitize_relative_path, unique_output_path};
+use crate::spec::{ProcessSpec, Reshaped};
+use crate::table::{write_rows, Table};
+use crate::workbook::Sheet;
+use calamine::{Data, Range, Reader};
+use chrono::Local;
+use rayon::prelude::*;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Instant;
+use tracing::{info, warn};
+use xlsxwriter::{Workbook, Worksheet};
+
+// Outcome of converting a single uploaded workbook
+#[derive(Clone, Debug)]
+pub struct ProcessedWorkbook {
+    pub output_file: String,
+    pub sheet_name: String,
+    pub rows_written: usize,
+    // Rows dropped by the spec's dedupe step
+    pub duplicates_removed: usize,
+}
+
+// Receives progress while a ZIP archive is processed. Every method has a
+// no-op default so callers only implement what they track.
+pub trait Progress {
+    // Names of every entry in the archive, in index order
+    fn entries(&self, _names: Vec<String>) {}
+    fn entry_started(&self, _index: usize) {}
+    fn sheet_written(&self, _index: usize, _sheet: &str, _rows_written: usize) {}
+    fn entry_done(&self, _index: usize, _output_file: &str) {}
+    fn entry_failed(&self, _index: usize, _error: &str) {}
+    fn entry_skipped(&self, _index: usize) {}
+    // Checked between entries; returning true stops processing early
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+// Progress sink for callers that don't track progress
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+// Resolve the output directory, creating it if needed
+pub fn output_directory(dir_path: &str) -> Result<&str> {
+    // Use default directory if dir_path is empty
+    let path = if dir_path.is_empty() {
+        "output_files"
+    } else {
+        dir_path
+    };
+
+    // Create directory if it doesn't exist
+    if !Path::new(path).exists() {
+        fs::create_dir_all(path)?;
+    }
+
+    Ok(path)
+}
+
+// Process Excel files, reshaping the first sheet according to `spec`. The
+// output is named after `output_stem`, or `firstsheet<timestamp>` without
+// one, with a counter added when the name is taken.
+pub fn process_excel_files(
+    file_data: &[u8],
+    output_dir: &str,
+    output_stem: Option<&str>,
+    spec: &ProcessSpec,
+) -> Result<ProcessedWorkbook> {
+    let started = Instant::now();
+    let cursor = Cursor::new(file_data);
+
+    // Use `open_workbook_auto_from_rs` to read from an in-memory buffer
+    let mut workbook: calamine::Sheets<_> = calamine::open_workbook_auto_from_rs(cursor)?;
+
+    // Read the first sheet
+    let sheet_name = workbook
+        .sheet_names()
+        .first()
+        .cloned()
+        .ok_or_else(|| Error::Invalid("Workbook has no sheets".to_string()))?;
+    let range = workbook.worksheet_range(&sheet_name)?;
+
+    // An empty spec copies the sheet as it is
+    let reshaped = if spec.is_empty() {
+        None
+    } else {
+        let sheet = Sheet {
+            name: sheet_name.clone(),
+            range: range.clone(),
+        };
+        Some(spec.apply(Table::from_sheet(&sheet))?)
+    };
+    let parse_ms = started.elapsed().as_millis() as u64;
+
+    // Create a new output Excel file
+    let output_dir = output_directory(output_dir)?;
+    let output_stem = match output_stem {
+        Some(stem) => stem.to_string(),
+        None => format!("firstsheet{}", Local::now().format("%m%d%y%H%M%S")),
+    };
+    let output_file = unique_output_path(output_dir, &output_stem, "xlsx")?;
+
+    // Don't leave the claimed name behind as an empty file
+    let rows_written = match write_output(&output_file, &range, reshaped.as_ref()) {
+        Ok(rows_written) => rows_written,
+        Err(e) => {
+            let _ = fs::remove_file(&output_file);
+            return Err(e);
+        }
+    };
+    let duplicates_removed = reshaped.as_ref().map_or(0, |reshaped| reshaped.duplicates_removed);
+
+    info!(
+        output_file = %output_file,
+        sheet = %sheet_name,
+        rows_written,
+        duplicates_removed,
+        bytes_in = file_data.len(),
+        parse_ms,
+        write_ms = started.elapsed().as_millis() as u64 - parse_ms,
+        "Processed workbook"
+    );
+    Ok(ProcessedWorkbook {
+        output_file,
+        sheet_name,
+        rows_written,
+        duplicates_removed,
+    })
+}
+
+// Write the main sheet and any sheets the spec added, returning the number
+// of rows in the main sheet. A reshaped sheet keeps its cells native, so
+// cleaned numbers and dates stay numbers and dates; a sheet copied as it is
+// is written as text.
+fn write_output(output_file: &str, range: &Range<Data>, reshaped: Option<&Reshaped>) -> Result<usize> {
+    let workbook = Workbook::new(output_file)?;
+    let mut sheet = workbook.add_worksheet(None)?;
+
+    let Some(reshaped) = reshaped else {
+        write_text_rows(&mut sheet, range)?;
+        workbook.close()?;
+        return Ok(range.height());
+    };
+
+    let rows = reshaped.table.to_rows();
+    write_rows(&mut sheet, &rows)?;
+    for (name, rows) in &reshaped.extra_sheets {
+        let mut extra = workbook.add_worksheet(Some(name))?;
+        write_rows(&mut extra, rows)?;
+    }
+
+    workbook.close()?;
+    Ok(rows.len())
+}
+
+// Write every cell of a range as text
+fn write_text_rows(sheet: &mut Worksheet, range: &Range<Data>) -> Result<()> {
+    // Use parallel iteration to process the rows
+    let rows: Vec<(usize, &[Data])> = range.rows().enumerate().collect();
+    let data: Vec<(usize, usize, String)> = rows
+        .into_par_iter()
+        .flat_map(|(row_idx, row)| {
+            row.iter()
+                .enumerate()
+                .filter_map(move |(col_idx, cell)| {
+                    match cell {
+                        Data::String(s) => Some((row_idx, col_idx, s.clone())),
+                        Data::Float(f) => Some((row_idx, col_idx, f.to_string())),
+                        Data::Int(i) => Some((row_idx, col_idx, i.to_string())),
+                        Data::Bool(b) => Some((row_idx, col_idx, if *b { "TRUE".to_string() } else { "FALSE".to_string() })),
+                        Data::DateTime(d) => d.as_datetime().map(|naive_dt| {
+                            (row_idx, col_idx, naive_dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                        }),
+                        Data::Error(e) => Some((row_idx, col_idx, format!("Error: {:?}", e))),
+                        Data::Empty => None,
+                        _ => None,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    // Sequentially write the collected data
+    for (row_idx, col_idx, cell) in data {
+        sheet.write_string(row_idx as u32, col_idx as u16, &cell, None)?;
+    }
+    Ok(())
+}
+
+// Process every Excel entry of a ZIP archive, skipping the rest.
+// Entries that fail to convert are reported and skipped, unless every one
+// fails, e.g. because the spec names a column none of them has; the first
+// error is returned then. Oversized entries abort the whole archive.
+pub fn process_zip_archive(
+    archive_path: &Path,
+    output_dir: &str,
+    max_entry_bytes: u64,
+    spec: &ProcessSpec,
+    progress: &dyn Progress,
+) -> Result<Vec<ProcessedWorkbook>> {
+    let mut archive = open_zip_archive(archive_path)?;
+    check_zip_entries(&mut archive, max_entry_bytes)?;
+
+    // Names were validated by check_zip_entries; report them normalized
+    let names = (0..archive.len())
+        .map(|i| {
+            let name = archive.name_for_index(i).unwrap_or_default();
+            sanitize_relative_path(name).map(|path| path.to_string_lossy().into_owned())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    progress.entries(names.clone());
+
+    let mut processed_files = Vec::new();
+    let mut first_error = None;
+    for (i, file_name) in names.iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+
+        let mut file = archive.by_index(i)?;
+
+        if file_name.ends_with(".xlsx") || file_name.ends_with(".xls") {
+            progress.entry_started(i);
+
+            let file_data = read_zip_entry(&mut file, file_name, max_entry_bytes)?;
+
+            // Outputs are named after their entry
+            let stem = Path::new(file_name).file_stem().and_then(|stem| stem.to_str());
+            match process_excel_files(&file_data, output_dir, stem, spec) {
+                Ok(processed) => {
+                    progress.sheet_written(i, &processed.sheet_name, processed.rows_written);
+                    progress.entry_done(i, &processed.output_file);
+                    processed_files.push(processed);
+                }
+                Err(e) => {
+                    warn!(entry = %file_name, error = %e, "Failed to process ZIP entry");
+                    progress.entry_failed(i, &e.to_string());
+                    first_error.get_or_insert(e);
+                }
+            }
+        } else {
+            info!(entry = %file_name, "Skipping non-Excel ZIP entry");
+            progress.entry_skipped(i);
+        }
+    }
+
+    match first_error {
+        Some(e) if processed_files.is_empty() => Err(e),
+        _ => Ok(processed_files),
+    }
+}
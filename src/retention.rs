@@ -0,0 +1,159 @@
+use crate::error::Result;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// File kept in an output directory listing the names of its pinned files,
+// one per line, so pins survive restarts
+pub const PINS_FILE: &str = ".pinned";
+
+// Limits applied to an output directory; limits left unset are not enforced
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+    pub max_files: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_total_bytes.is_some() || self.max_files.is_some()
+    }
+}
+
+// Outcome of a retention sweep
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RetentionReport {
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+    // Files left in place, pinned ones included
+    pub kept: usize,
+}
+
+struct OutputFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+    pinned: bool,
+}
+
+// Remove files under `root` (recursively, leaving out the directories in
+// `skip_dirs` entirely) until the policy holds: first every file older than
+// the maximum age, then the oldest files until the size and count limits are
+// met. Files listed in their directory's PINS_FILE, and the paths in `keep`,
+// are never removed but still count towards the limits.
+pub fn apply_retention(
+    root: &str,
+    policy: &RetentionPolicy,
+    keep: &HashSet<PathBuf>,
+    skip_dirs: &[PathBuf],
+) -> Result<RetentionReport> {
+    let mut report = RetentionReport::default();
+    if !policy.is_enabled() || !Path::new(root).exists() {
+        return Ok(report);
+    }
+
+    let mut files = list_files(Path::new(root), skip_dirs)?;
+    // Oldest first
+    files.sort_by_key(|file| file.modified);
+
+    let now = SystemTime::now();
+    let mut total_bytes: u64 = files.iter().map(|file| file.size).sum();
+    let mut total_files = files.len();
+
+    for file in &files {
+        if file.pinned || keep.contains(&file.path) {
+            continue;
+        }
+
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        let expired = policy.max_age.is_some_and(|max_age| age > max_age);
+        let over_size = policy.max_total_bytes.is_some_and(|max| total_bytes > max);
+        let over_count = policy.max_files.is_some_and(|max| total_files > max);
+        if !(expired || over_size || over_count) {
+            continue;
+        }
+
+        match fs::remove_file(&file.path) {
+            Ok(()) => {
+                total_bytes -= file.size;
+                total_files -= 1;
+                report.freed_bytes += file.size;
+                report.removed.push(file.path.to_string_lossy().into_owned());
+            }
+            // Someone else may have removed it already; carry on with the rest
+            Err(e) => tracing::warn!(path = %file.path.display(), error = %e, "Failed to remove expired file"),
+        }
+    }
+
+    report.kept = total_files;
+    Ok(report)
+}
+
+// Every regular file below `root` other than hidden ones: the pin lists
+// themselves and workbooks that a replace is still writing. Entries that
+// vanish while they are listed, e.g. because they were deleted meanwhile,
+// are skipped.
+fn list_files(root: &Path, skip_dirs: &[PathBuf]) -> Result<Vec<OutputFile>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let pins = read_pins(&dir)?;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir != root => {
+                tracing::warn!(path = %dir.display(), error = %e, "Skipping unreadable directory");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let listed = entry.and_then(|entry| {
+                let metadata = entry.metadata()?;
+                let modified = metadata.modified()?;
+                Ok((entry, metadata, modified))
+            });
+            let (entry, metadata, modified) = match listed {
+                Ok(listed) => listed,
+                Err(e) => {
+                    tracing::warn!(path = %dir.display(), error = %e, "Skipping unreadable entry");
+                    continue;
+                }
+            };
+            if metadata.is_dir() {
+                if !skip_dirs.contains(&entry.path()) {
+                    pending.push(entry.path());
+                }
+            } else if metadata.is_file() && !entry.file_name().to_string_lossy().starts_with('.') {
+                files.push(OutputFile {
+                    path: entry.path(),
+                    modified,
+                    size: metadata.len(),
+                    pinned: entry.file_name().to_str().is_some_and(|name| pins.contains(name)),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Names of the pinned files in `dir`
+pub fn read_pins(dir: &Path) -> Result<HashSet<String>> {
+    match fs::read_to_string(dir.join(PINS_FILE)) {
+        Ok(text) => Ok(text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Replace the list of pinned files in `dir`
+pub fn write_pins(dir: &Path, pins: &HashSet<String>) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let sorted: BTreeSet<&String> = pins.iter().collect();
+    let text: String = sorted.into_iter().map(|name| format!("{}\n", name)).collect();
+    fs::write(dir.join(PINS_FILE), text)?;
+    Ok(())
+}
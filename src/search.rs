@@ -0,0 +1,189 @@
+use crate::error::Result;
+use crate::workbook::{cell_to_string, read_sheets, write_cell};
+use calamine::Data;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use tracing::{debug, info, Span};
+use xlsxwriter::Workbook;
+
+// A cell matching a search query
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchResult {
+    pub sheet_name: String,
+    pub row: usize,
+    pub col: usize,
+    pub value: String,
+    pub file: String,
+}
+
+// A workbook rewritten by a replace
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replacement {
+    pub file: String,
+    pub cells_changed: usize,
+}
+
+// A workbook a replace could not rewrite
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplaceFailure {
+    pub file: String,
+    pub error: String,
+}
+
+// Outcome of a replace across several workbooks, in input order; workbooks
+// without a match appear in neither list
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReplaceReport {
+    pub updated: Vec<Replacement>,
+    pub failed: Vec<ReplaceFailure>,
+}
+
+// Search every sheet of a workbook in parallel for the query string
+pub fn search_workbook(file_path: &str, query: &str) -> Result<Vec<SearchResult>> {
+    let started = Instant::now();
+    let sheets = read_sheets(file_path)?;
+    let parse_ms = started.elapsed().as_millis() as u64;
+
+    let results = sheets
+        .par_iter()
+        .flat_map_iter(|sheet| {
+            sheet.range.rows().enumerate().flat_map(move |(row_idx, row)| {
+                row.iter().enumerate().filter_map(move |(col_idx, cell)| {
+                    let cell_value = cell_to_string(cell);
+
+                    // Check if the cell value contains the query string
+                    cell_value.contains(query).then(|| SearchResult {
+                        sheet_name: sheet.name.clone(),
+                        row: row_idx,
+                        col: col_idx,
+                        value: cell_value,
+                        file: file_path.to_string(),
+                    })
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    debug!(
+        file = file_path,
+        matches = results.len(),
+        parse_ms,
+        search_ms = started.elapsed().as_millis() as u64 - parse_ms,
+        "Searched workbook"
+    );
+    Ok(results)
+}
+
+// Search several workbooks in parallel, keeping results in input order
+pub fn search_files(file_paths: &[String], query: &str) -> Result<Vec<SearchResult>> {
+    // Rayon threads don't inherit the caller's span; carry it over so
+    // per-file logs keep their request context
+    let span = Span::current();
+    let results = file_paths
+        .par_iter()
+        .map(|file_path| span.in_scope(|| search_workbook(file_path, query)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(results.into_iter().flatten().collect())
+}
+
+// Replace text in every sheet of a workbook and write it back in place.
+// Returns the number of cells changed; the file is left alone when that is 0.
+pub fn replace_in_workbook(file_path: &str, search: &str, replace: &str) -> Result<usize> {
+    let started = Instant::now();
+    let sheets = read_sheets(file_path)?;
+    let parse_ms = started.elapsed().as_millis() as u64;
+    let cells_changed: usize = sheets
+        .iter()
+        .map(|sheet| {
+            sheet
+                .range
+                .used_cells()
+                .filter(|(_, _, cell)| matches!(cell, Data::String(s) if s.contains(search)))
+                .count()
+        })
+        .sum();
+    if cells_changed == 0 {
+        debug!(file = file_path, parse_ms, "No cells to replace");
+        return Ok(0);
+    }
+
+    // Rewrite the string cells of each sheet in parallel
+    let updated_sheets: Vec<(String, Vec<Vec<Data>>)> = sheets
+        .into_par_iter()
+        .map(|sheet| {
+            let updated_rows = sheet
+                .range
+                .rows()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| match cell {
+                            Data::String(s) if s.contains(search) => Data::String(s.replace(search, replace)),
+                            _ => cell.clone(),
+                        })
+                        .collect()
+                })
+                .collect();
+            (sheet.name, updated_rows)
+        })
+        .collect();
+
+    // Write the updated data to a file next to the original and swap it in,
+    // so concurrent readers see either the old or the new workbook, never a
+    // half-written one
+    let dir = Path::new(file_path).parent().filter(|dir| !dir.as_os_str().is_empty());
+    let temp_file = tempfile::Builder::new()
+        .prefix(".replace")
+        .suffix(".xlsx")
+        .tempfile_in(dir.unwrap_or(Path::new(".")))?;
+    let workbook = Workbook::new(&temp_file.path().to_string_lossy())?;
+    for (sheet_name, updated_rows) in &updated_sheets {
+        let mut sheet = workbook.add_worksheet(Some(sheet_name))?;
+
+        for (row_idx, row) in updated_rows.iter().enumerate() {
+            for (col_idx, cell) in row.iter().enumerate() {
+                write_cell(&mut sheet, row_idx as u32, col_idx as u16, cell)?;
+            }
+        }
+    }
+
+    workbook.close()?;
+    fs::set_permissions(temp_file.path(), fs::metadata(file_path)?.permissions())?;
+    temp_file.persist(file_path).map_err(|e| e.error)?;
+    info!(
+        file = file_path,
+        cells_changed,
+        parse_ms,
+        write_ms = started.elapsed().as_millis() as u64 - parse_ms,
+        "Replaced text in workbook"
+    );
+    Ok(cells_changed)
+}
+
+// Replace text in several workbooks in parallel. Each workbook is rewritten
+// on its own, so one failing doesn't stop the others or undo their changes.
+pub fn replace_in_files(file_paths: &[String], search: &str, replace: &str) -> ReplaceReport {
+    let span = Span::current();
+    let outcomes: Vec<Result<usize>> = file_paths
+        .par_iter()
+        .map(|file_path| span.in_scope(|| replace_in_workbook(file_path, search, replace)))
+        .collect();
+
+    let mut report = ReplaceReport::default();
+    for (file_path, outcome) in file_paths.iter().zip(outcomes) {
+        match outcome {
+            Ok(0) => {}
+            Ok(cells_changed) => report.updated.push(Replacement {
+                file: file_path.clone(),
+                cells_changed,
+            }),
+            Err(e) => report.failed.push(ReplaceFailure {
+                file: file_path.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    report
+}
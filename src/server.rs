@@ -0,0 +1,1435 @@
+use crate::audit::{AuditAction, AuditEntry, AuditLog};
+use crate::auth::{require_auth, Authenticator, User};
+use crate::config::Config;
+use crate::jobs::{Job, JobEvent, JobRegistry, JobState};
+use crate::metrics::{CountingProgress, Metrics};
+use crate::pool::WorkerPool;
+use crate::upload::{save_upload, UploadError, UploadLimits};
+use crate::workspace::{FileInfo, Workspaces};
+use actix_files::Files; // For serving static files
+use actix_multipart::Multipart;
+use actix_web::middleware::from_fn;
+use actix_web::{web, App, Error, HttpResponse, HttpServer};
+use excel_handler::archive::{check_zip_entries, for_each_zip_workbook, is_excel_file, open_zip_archive};
+use excel_handler::{
+    apply_retention, diff_workbooks, join_workbooks, merge_workbooks, output_directory, output_path,
+    parse_aggregates, parse_clean, parse_clean_columns, parse_columns, parse_date_formats, parse_dedupe_columns,
+    parse_group_by, parse_sort, process_excel_files, process_zip_archive, replace_in_files, sanitize_file_name,
+    search_files as search_workbooks, split_workbook, unique_output_path, zip_files, Dedupe, DiffOptions,
+    DuplicateKeys, Filter, GroupBy, JoinKind, JoinOptions, Keep, MergeInput, MergeMode, MergeOptions, NoProgress,
+    ProcessSpec, Progress, RetentionPolicy, RetentionReport, SearchResult, SplitBy,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tokio::sync::broadcast;
+use tracing::{error, info, info_span, Instrument};
+use tracing_actix_web::TracingLogger;
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+#[derive(Serialize)]
+struct ApiResponse {
+    message: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct SearchQuery {
+    query: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct ReplaceRequest {
+    search: String,
+    replace: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct AuditQuery {
+    // RFC 3339 timestamp, e.g. 2025-02-01T00:00:00Z
+    since: Option<String>,
+    // Tracked file id...
+    file: Option<usize>,
+    // ...or file name, which also finds files that have since been deleted
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+struct UploadOptions {
+    // Process the upload as a background job and return its id immediately
+    #[serde(rename = "async", default)]
+    background: bool,
+    // Cleaning steps, e.g. "trim,collapse,title,numbers" (see `parse_clean`)
+    clean: Option<String>,
+    // Columns to clean; every column by default
+    clean_columns: Option<String>,
+    // Date formats to read text dates with, e.g. "%d/%m/%Y;%b %d, %Y"
+    date_formats: Option<String>,
+    // Rows to keep, e.g. `Amount > 1000 and Status = "Open"` (see `Filter`)
+    filter: Option<String>,
+    // Key columns for duplicate removal, or "*" for whole rows
+    dedupe: Option<String>,
+    #[serde(default)]
+    dedupe_keep: Keep,
+    // Write removed duplicates to a "Duplicates" sheet
+    #[serde(default)]
+    duplicates_sheet: bool,
+    // Summary sheet grouped by these columns, e.g. "Region,Status"
+    group_by: Option<String>,
+    // Summary columns, e.g. "sum(Amount),count(*)" (see `parse_aggregates`)
+    aggregate: Option<String>,
+    // Sort order, e.g. "Region,Amount desc" (see `parse_sort`)
+    sort: Option<String>,
+    #[serde(default)]
+    sort_ignore_case: bool,
+    // Columns to keep, e.g. "Name,C:Total" (see `parse_columns`)
+    columns: Option<String>,
+}
+
+impl UploadOptions {
+    fn spec(&self) -> Result<ProcessSpec, UploadError> {
+        let mut spec = ProcessSpec::default();
+        if self.clean.is_some() || self.date_formats.is_some() {
+            let mut clean = self.clean.as_deref().map(parse_clean).transpose()?.unwrap_or_default();
+            clean.columns = self.clean_columns.as_deref().map(parse_clean_columns).transpose()?.unwrap_or_default();
+            clean.date_formats = self.date_formats.as_deref().map(parse_date_formats).transpose()?.unwrap_or_default();
+            spec.clean = Some(clean);
+        }
+        if let Some(filter) = &self.filter {
+            spec.filter = Some(Filter::parse(filter)?);
+        }
+        if let Some(columns) = &self.dedupe {
+            spec.dedupe = Some(Dedupe {
+                columns: parse_dedupe_columns(columns)?,
+                keep: self.dedupe_keep,
+            });
+        }
+        spec.duplicates_sheet = self.duplicates_sheet;
+        if self.group_by.is_some() || self.aggregate.is_some() {
+            spec.group_by = Some(GroupBy {
+                columns: self.group_by.as_deref().map(parse_group_by).transpose()?.unwrap_or_default(),
+                aggregates: self.aggregate.as_deref().map(parse_aggregates).transpose()?.unwrap_or_default(),
+            });
+        }
+        if let Some(sort) = &self.sort {
+            spec.sort = parse_sort(sort)?;
+        }
+        spec.sort_ignore_case = self.sort_ignore_case;
+        if let Some(columns) = &self.columns {
+            spec.columns = parse_columns(columns)?;
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct MergeRequest {
+    #[serde(default)]
+    mode: MergeMode,
+    sheet: Option<String>,
+    #[serde(default)]
+    source_column: bool,
+}
+
+#[derive(Deserialize, Clone)]
+struct SplitRequest {
+    // Split by the distinct values of this column...
+    column: Option<String>,
+    // ...or into chunks of this many rows
+    rows: Option<usize>,
+    sheet: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+struct JoinRequest {
+    // Tracked file ids
+    left: usize,
+    right: usize,
+    // Key column on both sides, unless left_key/right_key are given
+    key: Option<String>,
+    left_key: Option<String>,
+    right_key: Option<String>,
+    left_sheet: Option<String>,
+    right_sheet: Option<String>,
+    #[serde(default)]
+    kind: JoinKind,
+    #[serde(default)]
+    duplicates: DuplicateKeys,
+    #[serde(default)]
+    loose_keys: bool,
+}
+
+#[derive(Deserialize, Clone)]
+struct DiffRequest {
+    // Tracked file ids of the old and the new version
+    a: usize,
+    b: usize,
+    // Match rows on this column instead of by position
+    key: Option<String>,
+    sheet: Option<String>,
+    #[serde(default)]
+    format: DiffFormat,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum DiffFormat {
+    // The changes as JSON
+    #[default]
+    Json,
+    // A copy of the new version with the changes highlighted
+    Xlsx,
+}
+
+#[derive(Serialize)]
+struct JobAccepted {
+    job_id: usize,
+    status: String,
+}
+
+// In-memory storage for files (for demonstration purposes)
+struct AppState {
+    workspaces: Workspaces,
+    workers: WorkerPool,
+    jobs: JobRegistry,
+    limits: UploadLimits,
+    audit: AuditLog,
+    metrics: Metrics,
+    retention: RetentionPolicy,
+}
+
+// Handler for uploading and processing Excel files
+async fn upload_files(
+    mut payload: Multipart,
+    options: web::Query<UploadOptions>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+    let spec = options.spec()?;
+
+    // Read the uploaded file
+    if let Some(field) = payload.next().await {
+        let mut field = field?;
+        let content_disposition = field.content_disposition();
+
+        // Extract filename from content disposition header
+        let file_name = content_disposition
+            .and_then(|cd| cd.get_filename())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| "unknown_file".to_string());
+        let file_name = sanitize_file_name(&file_name).map_err(UploadError::from)?;
+
+        // Extract file extension
+        let file_extension = Path::new(&file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        // Stream the file content to disk
+        let upload = save_upload(&mut field, data.limits.max_upload_bytes).await?;
+        let max_entry_bytes = data.limits.max_entry_bytes;
+        let output_dir = workspace.output_dir.clone();
+
+        // Log filename and extension
+        info!(file = %file_name, extension = %file_extension, user = %user.name, "Uploaded file");
+        let upload_bytes = upload.as_file().metadata().map(|m| m.len()).unwrap_or(0);
+        data.metrics.upload_received(&file_extension, upload_bytes);
+
+        // let is_zip = files.len() >= 4 && &files[0..4] == b"PK\x03\x04";
+        let is_zip = file_extension == "zip";
+
+        if options.background && (is_zip || file_extension == "xlsx" || file_extension == "xls") {
+            // Reject oversized ZIP entries before accepting the job
+            if is_zip {
+                let archive_path = upload.path().to_path_buf();
+                data.workers
+                    .run(move || {
+                        let mut archive = open_zip_archive(&archive_path)?;
+                        check_zip_entries(&mut archive, max_entry_bytes)
+                    })
+                    .await?
+                    .map_err(UploadError::from)?;
+            }
+
+            // Forget jobs that finished long ago, along with their bundles
+            let expired_bundles = data.jobs.prune();
+            if !expired_bundles.is_empty() {
+                web::block(move || remove_job_bundles(&expired_bundles)).await?;
+            }
+
+            let job = data.jobs.create(file_name.clone(), &workspace.name).ok_or_else(|| {
+                actix_web::error::ErrorServiceUnavailable("Too many active jobs, please retry later")
+            })?;
+            let job_id = job.id();
+            info!(job_id, file = %file_name, "Queued job");
+
+            // The job span is a child of the request span, so its logs carry the request id
+            let span = info_span!("job", job_id);
+            actix_web::rt::spawn(
+                run_upload_job(data.clone(), user.into_inner(), job, file_name, is_zip, upload, spec).instrument(span),
+            );
+
+            return Ok(HttpResponse::Accepted().json(JobAccepted {
+                job_id,
+                status: format!("/jobs/{}", job_id),
+            }));
+        }
+
+        if is_zip {
+            info!("Detected ZIP file, processing...");
+
+            // Handle ZIP file processing
+            let worker_data = data.clone();
+            let dedupe = spec.dedupe.is_some();
+            let (processed, zip_buffer) = data
+                .workers
+                .run(move || {
+                    let progress = CountingProgress::new(&worker_data.metrics, &NoProgress);
+                    let processed = process_zip_archive(upload.path(), &output_dir, max_entry_bytes, &spec, &progress)?;
+                    let processed_files: Vec<String> = processed.iter().map(|p| p.output_file.clone()).collect();
+                    let zip_buffer = zip_files(&processed_files)?;
+                    Ok::<_, excel_handler::Error>((processed, zip_buffer))
+                })
+                .await?
+                .map_err(UploadError::from)?;
+
+            let processed_files: Vec<String> = processed.iter().map(|p| p.output_file.clone()).collect();
+            let file_ids = workspace.register_files(&processed_files);
+            record_upload(&data, &user, file_ids, processed_files, file_name).await;
+            data.metrics.bytes_out.inc_by(zip_buffer.len() as u64);
+
+            let mut response = HttpResponse::Ok();
+            response.content_type("application/zip");
+            if dedupe {
+                // Summed over every entry
+                let duplicates_removed: usize = processed.iter().map(|p| p.duplicates_removed).sum();
+                response.insert_header(("X-Duplicates-Removed", duplicates_removed.to_string()));
+            }
+            return Ok(response.body(zip_buffer));
+        } else if file_extension == "xlsx" || file_extension == "xls" {
+            info!("Detected Excel file, processing...");
+
+            // Process non-ZIP Excel file
+            let dedupe = spec.dedupe.is_some();
+            let processed = data
+                .workers
+                .run(move || {
+                    let files = fs::read(upload.path()).map_err(|e| e.to_string())?;
+                    process_excel_files(&files, &output_dir, None, &spec).map_err(|e| e.to_string())
+                })
+                .await?;
+
+            match processed {
+                Ok(processed) => {
+                    data.metrics.files_processed.inc();
+                    data.metrics.rows_written.inc_by(processed.rows_written as u64);
+                    let output_file = processed.output_file;
+                    let file_ids = workspace.register_files(std::slice::from_ref(&output_file));
+                    record_upload(&data, &user, file_ids, vec![output_file.clone()], file_name).await;
+
+                    let zip_buffer = data
+                        .workers
+                        .run(move || zip_files(&[output_file]))
+                        .await?
+                        .map_err(actix_web::error::ErrorInternalServerError)?;
+                    data.metrics.bytes_out.inc_by(zip_buffer.len() as u64);
+                    let mut response = HttpResponse::Ok();
+                    response.content_type("application/zip");
+                    if dedupe {
+                        response.insert_header(("X-Duplicates-Removed", processed.duplicates_removed.to_string()));
+                    }
+                    return Ok(response.body(zip_buffer));
+                }
+                Err(e) => {
+                    data.metrics.files_failed.inc();
+                    error!(error = %e, "Failed to process file");
+                    return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                        message: format!("Failed to process file: {}", e),
+                    }));
+                }
+            }
+        } else {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                message: format!("Unsupported file type: {}", file_extension),
+            }));
+        }
+    }
+
+    Ok(HttpResponse::BadRequest().json(ApiResponse {
+        message: "No files uploaded".to_string(),
+    }))
+}
+
+// Handler merging uploaded workbooks, or ZIPs of workbooks, into one workbook
+async fn merge_files(
+    mut payload: Multipart,
+    request: web::Query<MergeRequest>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+
+    // Store every uploaded file before merging them in upload order
+    let mut uploads = Vec::new();
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let file_name = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| "unknown_file".to_string());
+        let file_name = sanitize_file_name(&file_name).map_err(UploadError::from)?;
+        let file_extension = Path::new(&file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let is_zip = file_extension == "zip";
+        if !is_zip && !is_excel_file(&file_name) {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                message: format!("Unsupported file type: {}", file_name),
+            }));
+        }
+
+        let upload = save_upload(&mut field, data.limits.max_upload_bytes).await?;
+        let upload_bytes = upload.as_file().metadata().map(|m| m.len()).unwrap_or(0);
+        data.metrics.upload_received(&file_extension, upload_bytes);
+        uploads.push((file_name, is_zip, upload));
+    }
+
+    if uploads.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse {
+            message: "No files uploaded".to_string(),
+        }));
+    }
+    let upload_names: Vec<String> = uploads.iter().map(|(name, _, _)| name.clone()).collect();
+    info!(files = ?upload_names, mode = ?request.mode, user = %user.name, "Merging files");
+
+    let options = MergeOptions {
+        mode: request.mode,
+        sheet: request.sheet.clone(),
+        source_column: request.source_column,
+    };
+    let max_entry_bytes = data.limits.max_entry_bytes;
+    let output_dir = workspace.output_dir.clone();
+    let (merged, workbook) = data
+        .workers
+        .run(move || {
+            let mut inputs = Vec::new();
+            for (file_name, is_zip, upload) in &uploads {
+                if *is_zip {
+                    for_each_zip_workbook(upload.path(), max_entry_bytes, |name, file_data| {
+                        inputs.push(MergeInput::from_bytes(name, file_data)?);
+                        Ok(())
+                    })?;
+                } else {
+                    inputs.push(MergeInput::from_bytes(file_name.clone(), fs::read(upload.path())?)?);
+                }
+            }
+
+            let output_dir = output_directory(&output_dir)?;
+            let stem = format!("merged{}", chrono::Local::now().format("%m%d%y%H%M%S"));
+            let output_file = unique_output_path(output_dir, &stem, "xlsx")?;
+            let merged = merge_workbooks(&inputs, &options, &output_file).and_then(|merged| {
+                let workbook = fs::read(&merged.output_file)?;
+                Ok((merged, workbook))
+            });
+            // Don't leave the claimed name behind as an empty file
+            if merged.is_err() {
+                let _ = fs::remove_file(&output_file);
+            }
+            merged
+        })
+        .await?
+        .map_err(UploadError::from)?;
+
+    let file_ids = workspace.register_files(std::slice::from_ref(&merged.output_file));
+    let mut entry = AuditEntry::new(&user, AuditAction::Merge, file_ids, vec![merged.output_file.clone()]);
+    entry.upload = Some(upload_names.join(", "));
+    record_audit(&data, entry).await;
+    data.metrics.rows_written.inc_by(merged.rows_written as u64);
+    data.metrics.bytes_out.inc_by(workbook.len() as u64);
+
+    let download_name = Path::new(&merged.output_file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok()
+        .content_type(XLSX_CONTENT_TYPE)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", download_name)))
+        .body(workbook))
+}
+
+// Handler for joining two tracked files into a new workbook
+async fn join_files(
+    request: web::Query<JoinRequest>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+    let request = request.into_inner();
+    let (Some(left_key), Some(right_key)) = (
+        request.left_key.or(request.key.clone()),
+        request.right_key.or(request.key),
+    ) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse {
+            message: "Give key, or both left_key and right_key".to_string(),
+        }));
+    };
+
+    let (left_file, right_file) = {
+        let files = workspace.files.lock().unwrap();
+        (
+            files.get(&request.left).map(|file_info| file_info.name.clone()),
+            files.get(&request.right).map(|file_info| file_info.name.clone()),
+        )
+    };
+    let (Some(left_file), Some(right_file)) = (left_file, right_file) else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "File not found".to_string(),
+        }));
+    };
+    info!(left = %left_file, right = %right_file, kind = ?request.kind, user = %user.name, "Joining files");
+
+    let _timer = data.metrics.operation_seconds.with_label_values(&["join"]).start_timer();
+    let options = JoinOptions {
+        kind: request.kind,
+        left_key,
+        right_key,
+        left_sheet: request.left_sheet,
+        right_sheet: request.right_sheet,
+        duplicates: request.duplicates,
+        loose_keys: request.loose_keys,
+    };
+    let output_dir = workspace.output_dir.clone();
+    let inputs = format!("{}, {}", left_file, right_file);
+    let (joined, workbook) = data
+        .workers
+        .run(move || {
+            let output_dir = output_directory(&output_dir)?;
+            let stem = format!("joined{}", chrono::Local::now().format("%m%d%y%H%M%S"));
+            let output_file = unique_output_path(output_dir, &stem, "xlsx")?;
+            let joined = join_workbooks(&left_file, &right_file, &options, &output_file).and_then(|joined| {
+                let workbook = fs::read(&joined.output_file)?;
+                Ok((joined, workbook))
+            });
+            // Don't leave the claimed name behind as an empty file
+            if joined.is_err() {
+                let _ = fs::remove_file(&output_file);
+            }
+            joined
+        })
+        .await?
+        .map_err(UploadError::from)?;
+
+    let file_ids = workspace.register_files(std::slice::from_ref(&joined.output_file));
+    let mut entry = AuditEntry::new(&user, AuditAction::Join, file_ids, vec![joined.output_file.clone()]);
+    entry.upload = Some(inputs);
+    record_audit(&data, entry).await;
+    data.metrics.rows_written.inc_by(joined.rows_written as u64);
+    data.metrics.bytes_out.inc_by(workbook.len() as u64);
+
+    let download_name = Path::new(&joined.output_file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok()
+        .content_type(XLSX_CONTENT_TYPE)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", download_name)))
+        .insert_header(("X-Rows-Matched", joined.matched.to_string()))
+        .insert_header(("X-Left-Only", joined.left_only.to_string()))
+        .insert_header(("X-Right-Only", joined.right_only.to_string()))
+        .body(workbook))
+}
+
+// Handler for comparing two tracked files
+async fn diff_files(
+    request: web::Query<DiffRequest>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+    let request = request.into_inner();
+    let (a_file, b_file) = {
+        let files = workspace.files.lock().unwrap();
+        (
+            files.get(&request.a).map(|file_info| file_info.name.clone()),
+            files.get(&request.b).map(|file_info| file_info.name.clone()),
+        )
+    };
+    let (Some(a_file), Some(b_file)) = (a_file, b_file) else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "File not found".to_string(),
+        }));
+    };
+    info!(a = %a_file, b = %b_file, key = ?request.key, user = %user.name, "Comparing files");
+
+    let _timer = data.metrics.operation_seconds.with_label_values(&["diff"]).start_timer();
+    let options = DiffOptions {
+        key: request.key,
+        sheet: request.sheet,
+    };
+    let format = request.format;
+    let (diff, report) = data
+        .workers
+        .run(move || {
+            if format == DiffFormat::Json {
+                return Ok((diff_workbooks(&a_file, &b_file, &options, None)?, None));
+            }
+            // The report only lives long enough to be sent
+            let report_file = tempfile::Builder::new().suffix(".xlsx").tempfile()?;
+            let report_path = report_file.path().to_string_lossy().into_owned();
+            let diff = diff_workbooks(&a_file, &b_file, &options, Some(&report_path))?;
+            Ok::<_, excel_handler::Error>((diff, Some(fs::read(&report_path)?)))
+        })
+        .await?
+        .map_err(UploadError::from)?;
+
+    match report {
+        Some(report) => {
+            data.metrics.bytes_out.inc_by(report.len() as u64);
+            Ok(HttpResponse::Ok()
+                .content_type(XLSX_CONTENT_TYPE)
+                .insert_header(("Content-Disposition", "attachment; filename=\"diff.xlsx\""))
+                .body(report))
+        }
+        None => Ok(HttpResponse::Ok().json(diff)),
+    }
+}
+
+// Handler for splitting a tracked file into a ZIP of smaller workbooks
+async fn split_file(
+    request: web::Query<SplitRequest>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    index: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    let index = index.into_inner();
+    let workspace = data.workspaces.get(&user.workspace);
+    let by = match (&request.column, request.rows) {
+        (Some(column), None) => SplitBy::Column(column.clone()),
+        (None, Some(rows)) => SplitBy::Rows(rows),
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                message: "Give either column or rows".to_string(),
+            }))
+        }
+    };
+
+    let file_info = workspace.files.lock().unwrap().get(&index).cloned();
+    let Some(file_info) = file_info else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "File not found".to_string(),
+        }));
+    };
+    info!(file = %file_info.name, by = ?by, user = %user.name, "Splitting file");
+
+    let _timer = data.metrics.operation_seconds.with_label_values(&["split"]).start_timer();
+    let sheet = request.sheet.clone();
+    let file_path = file_info.name.clone();
+    let zip_buffer = data
+        .workers
+        .run(move || {
+            // Pieces only live long enough to be zipped
+            let temp_dir = tempfile::tempdir()?;
+            let pieces = split_workbook(&file_path, sheet.as_deref(), &by, &temp_dir.path().to_string_lossy())?;
+            zip_files(&pieces)
+        })
+        .await?
+        .map_err(UploadError::from)?;
+    data.metrics.bytes_out.inc_by(zip_buffer.len() as u64);
+
+    let stem = Path::new(&file_info.name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}_split.zip\"", stem)))
+        .body(zip_buffer))
+}
+
+// Process an upload in the background, recording progress on its job
+async fn run_upload_job(
+    data: web::Data<AppState>,
+    user: User,
+    job: Arc<Job>,
+    file_name: String,
+    is_zip: bool,
+    upload: NamedTempFile,
+    spec: ProcessSpec,
+) {
+    let workspace = data.workspaces.get(&user.workspace);
+    let worker_job = job.clone();
+    let upload_name = file_name.clone();
+    let max_entry_bytes = data.limits.max_entry_bytes;
+    let output_dir = workspace.output_dir.clone();
+    let worker_data = data.clone();
+    let dedupe = spec.dedupe.is_some();
+    let result = data
+        .workers
+        .run_when_ready(move || {
+            // None when the job was cancelled before it started
+            let job = worker_job;
+            if job.is_cancelled() {
+                return Ok(None);
+            }
+            job.start();
+            let progress = CountingProgress::new(&worker_data.metrics, job.as_ref());
+
+            if is_zip {
+                return process_zip_archive(upload.path(), &output_dir, max_entry_bytes, &spec, &progress)
+                    .map(Some)
+                    .map_err(|e| e.to_string());
+            }
+
+            progress.entries(vec![file_name]);
+            progress.entry_started(0);
+            let files = fs::read(upload.path()).map_err(|e| e.to_string())?;
+            match process_excel_files(&files, &output_dir, None, &spec) {
+                Ok(processed) => {
+                    progress.sheet_written(0, &processed.sheet_name, processed.rows_written);
+                    progress.entry_done(0, &processed.output_file);
+                    Ok(Some(vec![processed]))
+                }
+                Err(e) => {
+                    progress.entry_failed(0, &e.to_string());
+                    Err(format!("Failed to process file: {}", e))
+                }
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    let processed = match result {
+        Ok(Some(processed)) => processed,
+        // Nothing was written, so there is nothing to audit
+        Ok(None) => {
+            info!("Job cancelled before it started");
+            job.mark_cancelled();
+            return;
+        }
+        Err(e) => {
+            error!(error = %e, "Job failed");
+            job.fail(e);
+            return;
+        }
+    };
+    if dedupe {
+        job.set_duplicates_removed(processed.iter().map(|p| p.duplicates_removed).sum());
+    }
+    let processed_files: Vec<String> = processed.into_iter().map(|p| p.output_file).collect();
+
+    // Keep whatever finished before a cancellation
+    let file_ids = workspace.register_files(&processed_files);
+    record_upload(&data, &user, file_ids, processed_files.clone(), upload_name).await;
+    if job.is_cancelled() {
+        info!("Job cancelled");
+        job.mark_cancelled();
+        return;
+    }
+
+    // Bundle the results so they can be downloaded later
+    let job_id = job.id();
+    let bundle = job_result_path(&workspace.output_dir, job_id);
+    job.set_bundle(bundle.clone());
+    let bundled = data
+        .workers
+        .run_when_ready(move || {
+            let zip_buffer = zip_files(&processed_files).map_err(|e| e.to_string())?;
+            fs::write(bundle, zip_buffer).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    match bundled {
+        Ok(()) => job.finish(format!("/jobs/{}/download", job_id)),
+        Err(e) => job.fail(format!("Failed to bundle results: {}", e)),
+    }
+}
+
+async fn record_upload(
+    data: &web::Data<AppState>,
+    user: &User,
+    file_ids: Vec<usize>,
+    files: Vec<String>,
+    upload: String,
+) {
+    let mut entry = AuditEntry::new(user, AuditAction::Upload, file_ids, files);
+    entry.upload = Some(upload);
+    record_audit(data, entry).await;
+}
+
+// Append to the audit log off the executor; like `AuditLog::record`, a
+// failure is logged rather than failing the request
+async fn record_audit(data: &web::Data<AppState>, entry: AuditEntry) {
+    let data = data.clone();
+    if let Err(e) = web::block(move || data.audit.record(entry)).await {
+        error!(error = %e, "Failed to record audit entry");
+    }
+}
+
+// Location of the ZIP bundle produced by a finished job
+fn job_result_path(output_dir: &str, job_id: usize) -> String {
+    let jobs_dir = format!("{}/jobs", output_dir);
+    // A missing directory surfaces as an error on the following read or write
+    let _ = output_directory(&jobs_dir);
+    format!("{}/job{}.zip", jobs_dir, job_id)
+}
+
+// Delete the result bundles of jobs that were forgotten
+fn remove_job_bundles(bundles: &[String]) {
+    for bundle in bundles {
+        if let Err(e) = fs::remove_file(bundle) {
+            // Already gone, e.g. removed by hand
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!(path = %bundle, error = %e, "Failed to remove job bundle");
+            }
+        }
+    }
+}
+
+// Handler for polling the state of a background job
+async fn get_job(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    match data.jobs.get(id.into_inner(), &user.workspace) {
+        Some(job) => Ok(HttpResponse::Ok().json(job.status())),
+        None => Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "Job not found".to_string(),
+        })),
+    }
+}
+
+// Handler for cancelling a queued or running job
+async fn cancel_job(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    match data.jobs.get(id.into_inner(), &user.workspace) {
+        Some(job) if job.cancel() => Ok(HttpResponse::Ok().json(ApiResponse {
+            message: "Job cancellation requested".to_string(),
+        })),
+        Some(_) => Ok(HttpResponse::Conflict().json(ApiResponse {
+            message: "Job already finished".to_string(),
+        })),
+        None => Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "Job not found".to_string(),
+        })),
+    }
+}
+
+// Handler streaming job progress as Server-Sent Events
+async fn job_events(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    let job = match data.jobs.get(id.into_inner(), &user.workspace) {
+        Some(job) => job,
+        None => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse {
+                message: "Job not found".to_string(),
+            }))
+        }
+    };
+
+    // Start with the current state so late subscribers can render it
+    let (status, receiver) = job.subscribe();
+    let snapshot = sse_event("status", &status);
+    let finished = status.state.is_finished().then(|| JobEvent::finished(&status));
+
+    let updates = futures::stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let chunk = sse_event(event.name(), &event);
+                    let next = if event.is_final() { None } else { Some(receiver) };
+                    return Some((chunk, next));
+                }
+                // A slow client missed some events; carry on with the next one
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let stream = match finished {
+        Some(event) => {
+            let events = vec![snapshot, sse_event(event.name(), &event)];
+            futures::stream::iter(events).left_stream()
+        }
+        None => futures::stream::once(async move { snapshot }).chain(updates).right_stream(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream.map(Ok::<_, Error>)))
+}
+
+// Encode a single Server-Sent Event
+fn sse_event<T: Serialize>(name: &str, payload: &T) -> web::Bytes {
+    let data = serde_json::to_string(payload).unwrap_or_default();
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+}
+
+// Handler for downloading the result bundle of a finished job
+async fn download_job(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    let job_id = id.into_inner();
+    let job = match data.jobs.get(job_id, &user.workspace) {
+        Some(job) => job,
+        None => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse {
+                message: "Job not found".to_string(),
+            }))
+        }
+    };
+
+    if job.status().state != JobState::Done {
+        return Ok(HttpResponse::Conflict().json(ApiResponse {
+            message: "Job has not finished yet".to_string(),
+        }));
+    }
+
+    let output_dir = data.workspaces.get(&user.workspace).output_dir.clone();
+    let zip_buffer = data
+        .workers
+        .run(move || fs::read(job_result_path(&output_dir, job_id)))
+        .await?
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read job result: {}", e)))?;
+    data.metrics.bytes_out.inc_by(zip_buffer.len() as u64);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .body(zip_buffer))
+}
+
+// Handler for find and replace
+async fn find_and_replace(
+    replace_request: web::Query<ReplaceRequest>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+    let _timer = data.metrics.operation_seconds.with_label_values(&["replace"]).start_timer();
+    let search = replace_request.search.clone();
+    let replace = replace_request.replace.clone();
+
+    // Snapshot the tracked files so the lock isn't held while workbooks are rewritten
+    let file_list: Vec<(usize, FileInfo)> = workspace
+        .files
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, file_info)| (*id, file_info.clone()))
+        .collect();
+    let file_paths: Vec<String> = file_list.iter().map(|(_, file_info)| file_info.name.clone()).collect();
+
+    let report = data
+        .workers
+        .run(move || replace_in_files(&file_paths, &search, &replace))
+        .await?;
+    let replacements = &report.updated;
+    let updated_files = replacements.len();
+
+    let mut entry = AuditEntry::new(
+        &user,
+        AuditAction::Replace,
+        replacements
+            .iter()
+            .filter_map(|r| file_list.iter().find(|(_, file_info)| file_info.name == r.file).map(|(id, _)| *id))
+            .collect(),
+        replacements.iter().map(|r| r.file.clone()).collect(),
+    );
+    entry.search = Some(replace_request.search.clone());
+    entry.replace = Some(replace_request.replace.clone());
+    entry.cells_changed = Some(replacements.iter().map(|r| r.cells_changed).sum());
+    // Workbooks rewritten before another one failed stay rewritten, so they
+    // are audited either way
+    record_audit(&data, entry).await;
+
+    if !report.failed.is_empty() {
+        for failure in &report.failed {
+            error!(file = %failure.file, error = %failure.error, "Failed to replace text");
+        }
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse {
+            message: format!(
+                "Updated {} files, failed to update {}: {}",
+                updated_files,
+                report.failed.len(),
+                report.failed[0].error
+            ),
+        }));
+    }
+    if updated_files == 0 {
+        Ok(HttpResponse::Ok().json(ApiResponse {
+            message: "No files updated".to_string(),
+        }))
+    } else {
+        Ok(HttpResponse::Ok().json(ApiResponse {
+            message: format!("Updated {} files", updated_files),
+        }))
+    }
+}
+
+// Handler for deleting a file
+async fn delete_file(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    index: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    let index = index.into_inner();
+    let workspace = data.workspaces.get(&user.workspace);
+
+    // Check if the file exists in the in-memory storage
+    let file_info = workspace.files.lock().unwrap().remove(&index);
+    if let Some(file_info) = file_info {
+        // Delete the file from the filesystem
+        let file_path = file_info.name.clone();
+        let worker_workspace = workspace.clone();
+        let removed = data
+            .workers
+            .run(move || {
+                fs::remove_file(&file_path)?;
+                worker_workspace.unpin_removed(&file_path);
+                Ok::<_, std::io::Error>(())
+            })
+            .await?;
+        if removed.is_ok() {
+            record_audit(
+                &data,
+                AuditEntry::new(&user, AuditAction::Delete, vec![index], vec![file_info.name]),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                message: "File deleted successfully".to_string(),
+            }))
+        } else {
+            // If file deletion fails, reinsert the file into the in-memory storage
+            workspace.files.lock().unwrap().insert(index, file_info);
+            Ok(HttpResponse::InternalServerError().json(ApiResponse {
+                message: "Failed to delete file from the filesystem".to_string(),
+            }))
+        }
+    } else {
+        Ok(HttpResponse::NotFound().json(ApiResponse {
+            message: "File not found".to_string(),
+        }))
+    }
+}
+
+// Handler for querying the audit log of the caller's workspace
+async fn get_audit(
+    query: web::Query<AuditQuery>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let since = match query.since.as_deref().map(chrono::DateTime::parse_from_rfc3339).transpose() {
+        Ok(since) => since,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                message: format!("Invalid since timestamp: {}", e),
+            }))
+        }
+    };
+
+    // Ids don't survive a restart, so history is looked up by file name
+    let file = match query.file {
+        Some(id) => {
+            let workspace = data.workspaces.get(&user.workspace);
+            let file_name = workspace.files.lock().unwrap().get(&id).map(|file_info| file_info.name.clone());
+            match file_name {
+                Some(file_name) => Some(file_name),
+                None => {
+                    return Ok(HttpResponse::NotFound().json(ApiResponse {
+                        message: "File not found".to_string(),
+                    }))
+                }
+            }
+        }
+        None => query.name.clone(),
+    };
+
+    let data_ref = data.clone();
+    let workspace = user.workspace.clone();
+    let entries = data
+        .workers
+        .run(move || data_ref.audit.query(&workspace, since, file.as_deref()))
+        .await?
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read audit log: {}", e)))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "data": entries,
+        "count": entries.len()
+    })))
+}
+
+#[derive(Serialize)]
+struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl Check {
+    fn new(ok: bool, detail: Option<String>) -> Self {
+        Check { ok, detail }
+    }
+}
+
+// Liveness probe: the process is up and serving requests
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+// Readiness probe: the output directory is writable, the file registry is
+// usable and the worker pool has room for more work
+async fn readyz(data: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    // Probe with a real file; it is removed again when dropped
+    let output_dir = data.workspaces.root_dir().to_string();
+    let output_dir = web::block(move || {
+        output_directory(&output_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|dir| NamedTempFile::new_in(dir).map_err(|e| e.to_string()))
+            .map(|_| ())
+    })
+    .await?;
+    let output_dir = match output_dir {
+        Ok(()) => Check::new(true, None),
+        Err(e) => Check::new(false, Some(format!("Output directory is not writable: {}", e))),
+    };
+
+    let registry = Check::new(data.workspaces.is_healthy(), None);
+
+    let available = data.workers.available();
+    let workers = Check::new(
+        available > 0,
+        Some(format!("{} of {} slots free", available, data.workers.capacity())),
+    );
+
+    let ready = output_dir.ok && registry.ok && workers.ok;
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not ready" },
+        "checks": {
+            "output_dir": output_dir,
+            "registry": registry,
+            "workers": workers,
+        }
+    });
+    Ok(if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    })
+}
+
+// Handler exposing Prometheus metrics
+async fn get_metrics(data: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let body = data
+        .metrics
+        .render(data.jobs.active_count(), data.workspaces.tracked_files());
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
+}
+
+// Handlers for exempting a file from retention cleanup, or undoing that
+async fn pin_file(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    index: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    set_pinned(&data, &user, index.into_inner(), true).await
+}
+
+async fn unpin_file(
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    index: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    set_pinned(&data, &user, index.into_inner(), false).await
+}
+
+async fn set_pinned(data: &AppState, user: &User, index: usize, pinned: bool) -> Result<HttpResponse, Error> {
+    // Saving the pin list writes to disk
+    let workspace = data.workspaces.get(&user.workspace);
+    let saved = data.workers.run(move || workspace.set_pinned(index, pinned)).await?;
+    Ok(match saved {
+        Ok(true) => HttpResponse::Ok().json(ApiResponse {
+            message: if pinned { "File pinned" } else { "File unpinned" }.to_string(),
+        }),
+        Ok(false) => HttpResponse::NotFound().json(ApiResponse {
+            message: "File not found".to_string(),
+        }),
+        Err(e) => {
+            error!(error = %e, "Failed to save pinned files");
+            HttpResponse::InternalServerError().json(ApiResponse {
+                message: "Failed to save pinned files".to_string(),
+            })
+        }
+    })
+}
+
+// Handler for running retention cleanup immediately, limited to the caller's
+// workspace
+async fn cleanup(data: web::Data<AppState>, user: web::ReqData<User>) -> Result<HttpResponse, Error> {
+    if !data.retention.is_enabled() {
+        return Ok(HttpResponse::Conflict().json(ApiResponse {
+            message: "No retention limits are configured".to_string(),
+        }));
+    }
+
+    let output_dir = data.workspaces.get(&user.workspace).output_dir.clone();
+    let report = run_cleanup(data.clone(), Some(output_dir), false)
+        .await?
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Apply the retention policy to one workspace's directory, or to each
+// workspace in turn when none is given, and drop removed files from the
+// registries. The limits hold per workspace. Background sweeps wait for a
+// worker slot; manual runs are rejected when the pool is full.
+async fn run_cleanup(
+    data: web::Data<AppState>,
+    output_dir: Option<String>,
+    wait: bool,
+) -> Result<Result<RetentionReport, String>, Error> {
+    let policy = data.retention.clone();
+    // Bundles stay downloadable for as long as their job can be queried
+    let live_bundles = data.jobs.live_bundles();
+    let worker_data = data.clone();
+    let sweep = move || {
+        let workspaces = &worker_data.workspaces;
+        let sweep_all = output_dir.is_none();
+        let output_dirs = output_dir.map_or_else(|| workspaces.output_dirs(), |output_dir| vec![output_dir]);
+        let skip_dirs = [workspaces.workspaces_dir()];
+        let mut report = RetentionReport::default();
+        for output_dir in output_dirs {
+            match apply_retention(&output_dir, &policy, &live_bundles, &skip_dirs) {
+                Ok(swept) => {
+                    report.removed.extend(swept.removed);
+                    report.freed_bytes += swept.freed_bytes;
+                    report.kept += swept.kept;
+                }
+                // One workspace failing doesn't hold up the others
+                Err(e) if sweep_all => error!(output_dir = %output_dir, error = %e, "Retention sweep failed"),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(report)
+    };
+    let report = if wait {
+        data.workers.run_when_ready(sweep).await?
+    } else {
+        data.workers.run(sweep).await?
+    };
+
+    if let Ok(report) = &report {
+        for (workspace, id, file) in data.workspaces.forget(&report.removed) {
+            let user = User {
+                name: "retention".to_string(),
+                workspace,
+            };
+            record_audit(&data, AuditEntry::new(&user, AuditAction::Delete, vec![id], vec![file])).await;
+        }
+        info!(
+            removed = report.removed.len(),
+            freed_bytes = report.freed_bytes,
+            kept = report.kept,
+            "Retention sweep finished"
+        );
+    }
+    Ok(report)
+}
+
+// Sweep the output directory on a fixed interval for as long as the server runs
+async fn retention_task(data: web::Data<AppState>, interval_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        // The background sweep covers every workspace
+        match run_cleanup(data.clone(), None, true).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!(error = %e, "Retention sweep failed"),
+            Err(e) => error!(error = %e, "Retention sweep failed"),
+        }
+    }
+}
+
+// Handler for fetching the list of files
+async fn get_files(data: web::Data<AppState>, user: web::ReqData<User>) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+
+    // List the output directory off the executor
+    let output_dir = workspace.output_dir.clone();
+    let dir_files = data.workers.run(move || list_output_files(&output_dir)).await?;
+
+    let mut files_map = workspace.files.lock().unwrap();
+    let mut file_list: Vec<FileInfo> = files_map.values().cloned().collect();
+
+    for file_name in dir_files {
+        let exists = file_list.iter().any(|f| f.name == file_name);
+        if !exists {
+            file_list.push(workspace.file_info(file_name.clone()));
+
+            // Add to in-memory storage for consistency
+            let mut next_id = workspace.next_id.lock().unwrap();
+            files_map.insert(*next_id, workspace.file_info(file_name));
+            *next_id += 1;
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(file_list))
+}
+
+// Collect the paths of all regular files in the output directory
+fn list_output_files(output_dir: &str) -> Vec<String> {
+    let output_dir = match output_directory(output_dir) {
+        Ok(output_dir) => output_dir,
+        Err(_) => return Vec::new(),
+    };
+    let mut file_names = Vec::new();
+    if let Ok(entries) = fs::read_dir(output_dir) {
+        for entry in entries.flatten() {
+            if let Ok(metadata) = entry.metadata() {
+                // Hidden files, such as the pin list or a workbook being
+                // rewritten by a replace, are not listed
+                if metadata.is_file() && !entry.file_name().to_string_lossy().starts_with('.') {
+                    // Names that aren't valid UTF-8 or fail validation are not listed
+                    if let Some(path) = entry.file_name().to_str().and_then(|name| output_path(output_dir, name).ok()) {
+                        file_names.push(path);
+                    }
+                }
+            }
+        }
+    }
+    file_names
+}
+
+// Handler for searching across all tracked files
+async fn search_files(
+    query: web::Query<SearchQuery>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, Error> {
+    let workspace = data.workspaces.get(&user.workspace);
+    let _timer = data.metrics.operation_seconds.with_label_values(&["search"]).start_timer();
+    let query = query.query.clone(); // Extract the query string
+
+    // Snapshot the tracked files so the lock isn't held while searching
+    let file_list: Vec<FileInfo> = workspace.files.lock().unwrap().values().cloned().collect();
+
+    // Fan out across files (and their sheets) on the rayon pool
+    let results: Vec<SearchResult> = data
+        .workers
+        .run(move || {
+            let file_paths: Vec<String> = file_list.into_iter().map(|file_info| file_info.name).collect();
+            search_workbooks(&file_paths, &query)
+        })
+        .await?
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "data": results,
+        "count": results.len()
+        })))
+}
+
+pub async fn serve(config: Config) -> std::io::Result<()> {
+    // Initialize the shared state
+    let app_state = web::Data::new(AppState {
+        workspaces: Workspaces::new(config.storage.output_dir.clone()),
+        workers: match config.server.max_pending_jobs {
+            Some(capacity) => WorkerPool::new(capacity),
+            None => WorkerPool::with_default_capacity(),
+        },
+        jobs: JobRegistry::new(),
+        limits: config.upload.limits(),
+        metrics: Metrics::new(),
+        retention: config.retention.policy(),
+        audit: AuditLog::open(&config.storage.audit_log).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("Failed to open audit log {}: {}", config.storage.audit_log, e))
+        })?,
+    });
+    let authenticator = web::Data::new(Authenticator::new(&config.auth));
+    if !authenticator.is_enabled() {
+        tracing::warn!("Authentication is disabled; all requests share one workspace");
+    }
+    let static_dir = config.server.static_dir.clone();
+
+    if app_state.retention.is_enabled() {
+        actix_web::rt::spawn(retention_task(app_state.clone(), config.retention.interval_secs));
+    }
+
+    // Start the Actix-web server
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_state.clone()) // Share the state with all routes
+            .app_data(authenticator.clone())
+            // Every route, including the static UI, requires a known user
+            .wrap(from_fn(require_auth))
+            // Log every request with a request id, including rejected ones
+            .wrap(TracingLogger::default())
+            // API endpoint for file upload
+            .route("/upload", web::post().to(upload_files))
+            // API endpoint for merging several workbooks into one
+            .route("/merge", web::post().to(merge_files))
+            // API endpoint for joining two files on a key column
+            .route("/join", web::post().to(join_files))
+            // API endpoint for comparing two files
+            .route("/diff", web::get().to(diff_files))
+            // API endpoint for splitting a file into several workbooks
+            .route("/split/{index}", web::get().to(split_file))
+            // API endpoint for deleting a file
+            .route("/delete/{index}", web::delete().to(delete_file))
+            // API endpoints for pinning files so retention keeps them
+            .route("/pin/{index}", web::post().to(pin_file))
+            .route("/unpin/{index}", web::post().to(unpin_file))
+            // API endpoint for running retention cleanup now
+            .route("/cleanup", web::post().to(cleanup))
+            // API endpoint for fetching the list of files
+            .route("/files", web::get().to(get_files))
+            .route("/search", web::get().to(search_files)) // Add the search endpoint
+            // API endpoint for find and replace
+            .route("/replace", web::get().to(find_and_replace))
+            // API endpoint for the audit log
+            .route("/audit", web::get().to(get_audit))
+            // Prometheus scrape endpoint
+            .route("/metrics", web::get().to(get_metrics))
+            // Liveness and readiness probes, reachable without credentials
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+            // API endpoints for background upload jobs
+            .route("/jobs/{id}", web::get().to(get_job))
+            .route("/jobs/{id}/cancel", web::post().to(cancel_job))
+            .route("/jobs/{id}/events", web::get().to(job_events))
+            .route("/jobs/{id}/download", web::get().to(download_job))
+            // Serve static files from the configured directory
+            .service(Files::new("/", &static_dir).index_file("index.html"))
+    });
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    let (host, port) = config.bind_address();
+    info!("Listening on http://{}:{}", host, port);
+    server.bind((host, port))?.run().await
+}
@@ -0,0 +1,192 @@
+use crate::error::{Error, Result};
+use crate::table::Table;
+use crate::workbook::{cell_datetime, cell_number, cell_to_string};
+use calamine::Data;
+use chrono::NaiveDateTime;
+use std::cmp::Ordering;
+
+// A column to sort on, by header name or letter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+// Parse a sort list such as "Region,Amount desc". Each entry is a column
+// optionally followed by "asc" or "desc".
+pub fn parse_sort(list: &str) -> Result<Vec<SortKey>> {
+    let keys: Vec<SortKey> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (column, descending) = match entry.rsplit_once(char::is_whitespace) {
+                Some((column, order)) if order.eq_ignore_ascii_case("desc") => (column.trim_end(), true),
+                Some((column, order)) if order.eq_ignore_ascii_case("asc") => (column.trim_end(), false),
+                _ => (entry, false),
+            };
+            SortKey {
+                column: column.to_string(),
+                descending,
+            }
+        })
+        .collect();
+
+    if keys.is_empty() {
+        return Err(Error::Invalid("Sort list is empty".to_string()));
+    }
+    Ok(keys)
+}
+
+// Sort the data rows of a table; the header stays first. Numbers compare
+// numerically and dates chronologically, text sorts after both, and blank
+// cells sink to the bottom in either direction. Equal rows keep their order.
+pub fn sort_table(mut table: Table, keys: &[SortKey], ignore_case: bool) -> Result<Table> {
+    let columns = keys
+        .iter()
+        .map(|key| table.require_column(&key.column).map(|col| (col, key.descending)))
+        .collect::<Result<Vec<_>>>()?;
+    table.rows = sort_rows(table.rows, &columns, ignore_case);
+    Ok(table)
+}
+
+// Sort rows on (column index, descending) pairs, as `sort_table` does
+pub fn sort_rows(rows: Vec<Vec<Data>>, columns: &[(usize, bool)], ignore_case: bool) -> Vec<Vec<Data>> {
+    // Classify each sort cell once rather than on every comparison
+    let mut decorated: Vec<(Vec<SortValue>, Vec<Data>)> = rows
+        .into_iter()
+        .map(|row| {
+            let values = columns.iter().map(|&(col, _)| SortValue::new(&row[col], ignore_case)).collect();
+            (values, row)
+        })
+        .collect();
+
+    decorated.sort_by(|(a, _), (b, _)| {
+        columns
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(&(_, descending), (a, b))| a.compare(b, descending))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    decorated.into_iter().map(|(_, row)| row).collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum SortValue {
+    Number(f64),
+    Date(NaiveDateTime),
+    Bool(bool),
+    Text(String),
+    Empty,
+}
+
+impl SortValue {
+    fn new(cell: &Data, ignore_case: bool) -> Self {
+        if let Data::Bool(b) = cell {
+            return SortValue::Bool(*b);
+        }
+        if let Some(n) = cell_number(cell) {
+            return SortValue::Number(n);
+        }
+        if let Some(datetime) = cell_datetime(cell) {
+            return SortValue::Date(datetime);
+        }
+
+        let text = cell_to_string(cell);
+        if text.trim().is_empty() {
+            SortValue::Empty
+        } else if ignore_case {
+            SortValue::Text(text.to_lowercase())
+        } else {
+            SortValue::Text(text)
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortValue::Number(_) => 0,
+            SortValue::Date(_) => 1,
+            SortValue::Bool(_) => 2,
+            SortValue::Text(_) => 3,
+            SortValue::Empty => 4,
+        }
+    }
+
+    fn compare(&self, other: &Self, descending: bool) -> Ordering {
+        // Blanks go last whichever way the column sorts
+        match (self, other) {
+            (SortValue::Empty, SortValue::Empty) => return Ordering::Equal,
+            (SortValue::Empty, _) => return Ordering::Greater,
+            (_, SortValue::Empty) => return Ordering::Less,
+            _ => {}
+        }
+
+        // Kinds order first, then values; total_cmp keeps this a total order
+        let ordering = match (self, other) {
+            (SortValue::Number(a), SortValue::Number(b)) => a.total_cmp(b),
+            (SortValue::Date(a), SortValue::Date(b)) => a.cmp(b),
+            (SortValue::Bool(a), SortValue::Bool(b)) => a.cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Data {
+        Data::String(s.to_string())
+    }
+
+    fn sorted(cells: Vec<Data>, descending: bool, ignore_case: bool) -> Vec<String> {
+        let rows = cells.into_iter().map(|cell| vec![cell]).collect();
+        sort_rows(rows, &[(0, descending)], ignore_case).iter().map(|row| cell_to_string(&row[0])).collect()
+    }
+
+    #[test]
+    fn parses_sort_lists() {
+        let keys = parse_sort("Region, Amount desc,Due Date ASC").unwrap();
+        let keys: Vec<(&str, bool)> = keys.iter().map(|key| (key.column.as_str(), key.descending)).collect();
+        assert_eq!(keys, [("Region", false), ("Amount", true), ("Due Date", false)]);
+        assert!(parse_sort(" , ").is_err());
+    }
+
+    #[test]
+    fn orders_numbers_then_dates_then_text_with_blanks_last() {
+        let cells = vec![text("b"), Data::Empty, text("10"), Data::Float(9.5), text("2026-01-01"), text("a")];
+        assert_eq!(sorted(cells.clone(), false, false), ["9.5", "10", "2026-01-01", "a", "b", ""]);
+        assert_eq!(sorted(cells, true, false), ["b", "a", "2026-01-01", "10", "9.5", ""]);
+    }
+
+    #[test]
+    fn treats_nan_and_inf_as_text() {
+        let cells = vec![text("Nan"), Data::Int(3), text("inf"), Data::Int(1)];
+        assert_eq!(sorted(cells, false, false), ["1", "3", "Nan", "inf"]);
+    }
+
+    #[test]
+    fn ignores_case_on_request_and_keeps_ties_in_order() {
+        let cells = vec![text("b"), text("B"), text("a")];
+        assert_eq!(sorted(cells.clone(), false, false), ["B", "a", "b"]);
+        assert_eq!(sorted(cells, false, true), ["a", "b", "B"]);
+    }
+
+    #[test]
+    fn sorts_on_later_keys_when_earlier_ones_tie() {
+        let rows = vec![vec![text("x"), Data::Int(2)], vec![text("y"), Data::Int(1)], vec![text("x"), Data::Int(1)]];
+        let rows: Vec<String> = sort_rows(rows, &[(0, false), (1, true)], false)
+            .iter()
+            .map(|row| format!("{}{}", cell_to_string(&row[0]), cell_to_string(&row[1])))
+            .collect();
+        assert_eq!(rows, ["x2", "x1", "y1"]);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::aggregate::{aggregate_table, GroupBy, SUMMARY_SHEET};
+use crate::clean::{clean_table, Clean};
+use crate::dedupe::{dedupe_table, Dedupe, DUPLICATES_SHEET};
+use crate::error::{Error, Result};
+use crate::filter::Filter;
+use crate::sort::{sort_table, SortKey};
+use crate::table::Table;
+use calamine::Data;
+
+// A column to keep, by header name or letter, optionally under a new header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub source: String,
+    pub rename: Option<String>,
+}
+
+// How a sheet is reshaped before it is written out. The default spec copies
+// the sheet unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessSpec {
+    // Text cleaning, applied first so later steps see the cleaned values
+    pub clean: Option<Clean>,
+    // Rows to keep; evaluated against the original headers
+    pub filter: Option<Filter>,
+    // Duplicate removal, applied to the filtered rows in their original order
+    pub dedupe: Option<Dedupe>,
+    // Write removed duplicates to their own sheet
+    pub duplicates_sheet: bool,
+    // Summary of the remaining rows, written to its own sheet
+    pub group_by: Option<GroupBy>,
+    // Sort order, also by original header
+    pub sort: Vec<SortKey>,
+    // Compare text case-insensitively when sorting
+    pub sort_ignore_case: bool,
+    // Columns to keep, in output order; empty keeps every column
+    pub columns: Vec<ColumnSpec>,
+}
+
+// A table reshaped by a spec, plus any sheets the spec adds alongside it
+#[derive(Clone, Debug, Default)]
+pub struct Reshaped {
+    pub table: Table,
+    pub extra_sheets: Vec<(String, Vec<Vec<Data>>)>,
+    pub duplicates_removed: usize,
+    // Group-by summary, also included in `extra_sheets`
+    pub summary: Option<Table>,
+}
+
+impl ProcessSpec {
+    pub fn is_empty(&self) -> bool {
+        self.clean.is_none()
+            && self.filter.is_none()
+            && self.dedupe.is_none()
+            && self.group_by.is_none()
+            && self.sort.is_empty()
+            && self.columns.is_empty()
+    }
+
+    // Reshape a table according to the spec
+    pub fn apply(&self, table: Table) -> Result<Reshaped> {
+        let mut reshaped = Reshaped::default();
+        let table = match &self.clean {
+            Some(clean) => clean_table(table, clean)?,
+            None => table,
+        };
+        let mut table = match &self.filter {
+            Some(filter) => filter.apply(table)?,
+            None => table,
+        };
+        if let Some(dedupe) = &self.dedupe {
+            let (deduped, removed) = dedupe_table(table, dedupe)?;
+            reshaped.duplicates_removed = removed.len();
+            if self.duplicates_sheet {
+                let removed = Table {
+                    header: deduped.header.clone(),
+                    rows: removed,
+                };
+                reshaped.extra_sheets.push((DUPLICATES_SHEET.to_string(), removed.to_rows()));
+            }
+            table = deduped;
+        }
+        if let Some(group_by) = &self.group_by {
+            let summary = aggregate_table(&table, group_by)?;
+            reshaped.extra_sheets.push((SUMMARY_SHEET.to_string(), summary.to_rows()));
+            reshaped.summary = Some(summary);
+        }
+        if !self.sort.is_empty() {
+            table = sort_table(table, &self.sort, self.sort_ignore_case)?;
+        }
+        reshaped.table = self.select_columns(table)?;
+        Ok(reshaped)
+    }
+
+    fn select_columns(&self, table: Table) -> Result<Table> {
+        if self.columns.is_empty() {
+            return Ok(table);
+        }
+
+        let indices = self
+            .columns
+            .iter()
+            .map(|column| table.require_column(&column.source))
+            .collect::<Result<Vec<_>>>()?;
+        let header = self
+            .columns
+            .iter()
+            .zip(&indices)
+            .map(|(column, &col)| column.rename.clone().unwrap_or_else(|| table.header[col].clone()))
+            .collect();
+        let rows = table
+            .rows
+            .into_iter()
+            .map(|row| indices.iter().map(|&col| row[col].clone()).collect())
+            .collect();
+        Ok(Table { header, rows })
+    }
+}
+
+// Parse a column list such as "Name,C:Total,Region:Area". Each entry is a
+// header name or column letter, optionally followed by ":" and a new header.
+pub fn parse_columns(list: &str) -> Result<Vec<ColumnSpec>> {
+    let columns = list
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (source, rename) = match entry.split_once(':') {
+                Some((source, rename)) => (source.trim(), Some(rename.trim())),
+                None => (entry, None),
+            };
+            if source.is_empty() {
+                return Err(Error::Invalid(format!("Missing column before ':' in {:?}", entry)));
+            }
+            if rename == Some("") {
+                return Err(Error::Invalid(format!("Missing new header after ':' in {:?}", entry)));
+            }
+            Ok(ColumnSpec {
+                source: source.to_string(),
+                rename: rename.map(str::to_string),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if columns.is_empty() {
+        return Err(Error::Invalid("Column list is empty".to_string()));
+    }
+    Ok(columns)
+}
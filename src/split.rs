@@ -0,0 +1,150 @@
+use crate::error::{Error, Result};
+use crate::paths::unique_output_path;
+use crate::table::{write_workbook, Table};
+use crate::workbook::{cell_to_string, read_sheets, select_sheet};
+use calamine::Data;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// Upper bound on the files a single split may produce
+pub const MAX_SPLIT_PARTS: usize = 1000;
+
+// Longest file stem a part gets, in bytes; cell values can be far longer than
+// file systems allow names to be (usually 255 bytes)
+const MAX_STEM_BYTES: usize = 120;
+
+#[derive(Clone, Debug)]
+pub enum SplitBy {
+    // One file per distinct value of the named column
+    Column(String),
+    // Consecutive chunks of at most this many data rows
+    Rows(usize),
+}
+
+// Split one sheet of a workbook (the first unless named) into several
+// workbooks in `output_dir`, each starting with the header row. Existing
+// files are never overwritten; a part whose name is taken gets a numeric
+// suffix. Returns the paths written, in order of first appearance.
+pub fn split_workbook(file_path: &str, sheet: Option<&str>, by: &SplitBy, output_dir: &str) -> Result<Vec<String>> {
+    let sheets = read_sheets(file_path)?;
+    let sheet = select_sheet(&sheets, sheet)?;
+    let table = Table::from_sheet(sheet);
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|stem| truncate_stem(&stem.to_string_lossy()).to_string())
+        .unwrap_or_else(|| "split".to_string());
+
+    let parts: Vec<(String, Vec<Vec<Data>>)> = match by {
+        SplitBy::Column(column) => {
+            let col = table.require_column(column)?;
+            let mut order: Vec<String> = Vec::new();
+            let mut groups: HashMap<String, Vec<Vec<Data>>> = HashMap::new();
+            for row in &table.rows {
+                let value = cell_to_string(&row[col]).trim().to_string();
+                if !groups.contains_key(&value) {
+                    if order.len() == MAX_SPLIT_PARTS {
+                        return Err(too_many_parts());
+                    }
+                    order.push(value.clone());
+                }
+                groups.entry(value).or_default().push(row.clone());
+            }
+
+            let mut used = HashSet::new();
+            order
+                .into_iter()
+                .map(|value| {
+                    let rows = groups.remove(&value).unwrap_or_default();
+                    let label = if value.is_empty() { "blank".to_string() } else { value };
+                    (unique_file_name(&format!("{}_{}", stem, label), &mut used), rows)
+                })
+                .collect()
+        }
+        SplitBy::Rows(0) => return Err(Error::Invalid("Rows per file must be at least 1".to_string())),
+        SplitBy::Rows(rows) => {
+            if table.rows.len().div_ceil(*rows) > MAX_SPLIT_PARTS {
+                return Err(too_many_parts());
+            }
+            table
+                .rows
+                .chunks(*rows)
+                .enumerate()
+                .map(|(i, chunk)| (format!("{}_part{}", stem, i + 1), chunk.to_vec()))
+                .collect()
+        }
+    };
+
+    let mut output_files = Vec::with_capacity(parts.len());
+    for (part_stem, rows) in parts {
+        let output_file = unique_output_path(output_dir, &part_stem, "xlsx")?;
+        let part = Table {
+            header: table.header.clone(),
+            rows,
+        };
+        // Don't leave the claimed name behind as an empty file
+        if let Err(e) = write_workbook(&output_file, &[(sheet.name.clone(), part.to_rows())]) {
+            let _ = fs::remove_file(&output_file);
+            return Err(e);
+        }
+        output_files.push(output_file);
+    }
+
+    tracing::info!(file = file_path, parts = output_files.len(), "Split workbook");
+    Ok(output_files)
+}
+
+fn too_many_parts() -> Error {
+    Error::Invalid(format!("Split would produce more than {} files", MAX_SPLIT_PARTS))
+}
+
+// File stem for a part: characters that aren't safe in file names become
+// underscores, long values are cut short and repeats get a numeric suffix
+fn unique_file_name(base: &str, used: &mut HashSet<String>) -> String {
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_alphanumeric() || " -_.".contains(c) { c } else { '_' })
+        .collect();
+    let cleaned = truncate_stem(&cleaned).trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    let mut name = cleaned.to_string();
+    let mut n = 1;
+    while !used.insert(name.to_lowercase()) {
+        n += 1;
+        name = format!("{} ({})", cleaned, n);
+    }
+    name
+}
+
+// The longest prefix of `stem` within MAX_STEM_BYTES that ends on a
+// character boundary
+fn truncate_stem(stem: &str) -> &str {
+    if stem.len() <= MAX_STEM_BYTES {
+        return stem;
+    }
+    let end = (0..=MAX_STEM_BYTES).rev().find(|&end| stem.is_char_boundary(end)).unwrap_or(0);
+    &stem[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_names_are_safe_and_unique() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_file_name("sales_North/East", &mut used), "sales_North_East");
+        assert_eq!(unique_file_name("sales_north:east", &mut used), "sales_north_east (2)");
+        assert_eq!(unique_file_name("sales_..", &mut used), "sales_");
+    }
+
+    #[test]
+    fn long_values_are_cut_on_a_character_boundary() {
+        let mut used = HashSet::new();
+        let long = "é".repeat(200);
+        let name = unique_file_name(&long, &mut used);
+        assert!(name.len() <= MAX_STEM_BYTES);
+        assert_eq!(name, "é".repeat(MAX_STEM_BYTES / 2));
+        assert_eq!(unique_file_name(&format!("{}x", long), &mut used), format!("{} (2)", name));
+    }
+}
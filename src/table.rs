@@ -0,0 +1,100 @@
+use crate::error::{Error, Result};
+use crate::workbook::{cell_to_string, write_cell, Sheet};
+use calamine::Data;
+use xlsxwriter::{Workbook, Worksheet};
+
+// Excel's limit on rows per worksheet
+pub const MAX_ROWS: usize = 1_048_576;
+
+// A sheet split into its header row and data rows. Every row is padded to
+// the width of the header.
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<Data>>,
+}
+
+impl Table {
+    // Treat the first row of the sheet as the header
+    pub fn from_sheet(sheet: &Sheet) -> Self {
+        let mut rows = sheet.range.rows();
+        let header: Vec<String> = match rows.next() {
+            Some(header) => header.iter().map(|cell| cell_to_string(cell).trim().to_string()).collect(),
+            None => return Table::default(),
+        };
+
+        let rows = rows
+            .map(|row| {
+                let mut row = row.to_vec();
+                row.resize(header.len(), Data::Empty);
+                row
+            })
+            .collect();
+        Table { header, rows }
+    }
+
+    // Index of the first column with this header
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.header.iter().position(|header| header == name)
+    }
+
+    // Resolve a header name or, failing that, a column letter such as "B" or
+    // "AA". An unknown column is an error.
+    pub fn require_column(&self, name: &str) -> Result<usize> {
+        self.column(name)
+            .or_else(|| column_letter_index(name).filter(|&col| col < self.header.len()))
+            .ok_or_else(|| Error::Invalid(format!("Unknown column: {}", name)))
+    }
+
+    // Header followed by the data rows, ready to be written out
+    pub fn to_rows(&self) -> Vec<Vec<Data>> {
+        let header = self.header.iter().map(|name| Data::String(name.clone())).collect();
+        std::iter::once(header).chain(self.rows.iter().cloned()).collect()
+    }
+}
+
+// Zero-based index of an Excel column letter ("A" is 0, "AA" is 26)
+pub fn column_letter_index(letters: &str) -> Option<usize> {
+    if letters.is_empty() || letters.len() > 3 || !letters.bytes().all(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    let number = letters.bytes().fold(0, |n, b| n * 26 + (b - b'A') as usize + 1);
+    Some(number - 1)
+}
+
+// Whether every cell of a row is empty
+pub fn is_blank_row(row: &[Data]) -> bool {
+    row.iter().all(|cell| matches!(cell, Data::Empty))
+}
+
+// Write raw rows to a worksheet, keeping numbers and booleans native
+pub fn write_rows(sheet: &mut Worksheet, rows: &[Vec<Data>]) -> Result<()> {
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (col_idx, cell) in row.iter().enumerate() {
+            write_cell(sheet, row_idx as u32, col_idx as u16, cell)?;
+        }
+    }
+    Ok(())
+}
+
+// Write named sheets of raw rows to a new workbook and return the number of
+// rows written
+pub fn write_workbook(output_file: &str, sheets: &[(String, Vec<Vec<Data>>)]) -> Result<usize> {
+    if let Some((name, _)) = sheets.iter().find(|(_, rows)| rows.len() > MAX_ROWS) {
+        return Err(Error::Invalid(format!(
+            "Sheet {} has more than the {} rows Excel allows",
+            name, MAX_ROWS
+        )));
+    }
+
+    let workbook = Workbook::new(output_file)?;
+    let mut rows_written = 0;
+    for (name, rows) in sheets {
+        let mut sheet = workbook.add_worksheet(Some(name))?;
+        write_rows(&mut sheet, rows)?;
+        rows_written += rows.len();
+    }
+
+    workbook.close()?;
+    Ok(rows_written)
+}
@@ -0,0 +1,112 @@
+use actix_multipart::Field;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use futures_util::StreamExt;
+use serde_json::json;
+use excel_handler::DEFAULT_MAX_ENTRY_BYTES;
+use std::fmt;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 512 * 1024 * 1024;
+
+// Size limits applied to uploaded files and to the entries of uploaded ZIPs
+#[derive(Clone, Copy)]
+pub struct UploadLimits {
+    pub max_upload_bytes: u64,
+    pub max_entry_bytes: u64,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        UploadLimits {
+            max_upload_bytes: DEFAULT_MAX_UPLOAD_BYTES,
+            max_entry_bytes: DEFAULT_MAX_ENTRY_BYTES,
+        }
+    }
+}
+
+// Errors raised while storing or unpacking an upload
+#[derive(Debug)]
+pub enum UploadError {
+    TooLarge(String),
+    Invalid(String),
+    Failed(String),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::TooLarge(message) | UploadError::Invalid(message) | UploadError::Failed(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl ResponseError for UploadError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UploadError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::Invalid(_) => StatusCode::BAD_REQUEST,
+            UploadError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({ "message": self.to_string() }))
+    }
+}
+
+impl From<String> for UploadError {
+    fn from(message: String) -> Self {
+        UploadError::Failed(message)
+    }
+}
+
+impl From<excel_handler::Error> for UploadError {
+    fn from(error: excel_handler::Error) -> Self {
+        match error {
+            excel_handler::Error::EntryTooLarge { .. } => UploadError::TooLarge(error.to_string()),
+            excel_handler::Error::UnsafePath { .. } | excel_handler::Error::Invalid(_) | excel_handler::Error::Read(_) => {
+                UploadError::Invalid(error.to_string())
+            }
+            _ => UploadError::Failed(error.to_string()),
+        }
+    }
+}
+
+// Stream a multipart field into a temporary file without buffering it in memory.
+// The file is removed as soon as the returned handle is dropped.
+pub async fn save_upload(field: &mut Field, max_upload_bytes: u64) -> Result<NamedTempFile, actix_web::Error> {
+    let temp_file = NamedTempFile::new()
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to create temp file: {}", e)))?;
+    let std_file = temp_file
+        .reopen()
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to open temp file: {}", e)))?;
+    let mut writer = tokio::fs::File::from_std(std_file);
+
+    let mut written: u64 = 0;
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        written += chunk.len() as u64;
+        if written > max_upload_bytes {
+            return Err(UploadError::TooLarge(format!(
+                "Upload exceeds the maximum size of {} bytes",
+                max_upload_bytes
+            ))
+            .into());
+        }
+
+        writer
+            .write_all(&chunk)
+            .await
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to store upload: {}", e)))?;
+    }
+
+    writer
+        .flush()
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to store upload: {}", e)))?;
+    Ok(temp_file)
+}
@@ -0,0 +1,149 @@
+use crate::error::{Error, Result};
+use calamine::{open_workbook_auto_from_rs, Data, Range, Reader};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use xlsxwriter::{Format, Worksheet, XlsxError};
+
+// Formats recognised for dates stored as text
+pub const DATETIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"];
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Excel number formats given to date and duration cells
+const DATETIME_NUM_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+const DURATION_NUM_FORMAT: &str = "[h]:mm:ss";
+
+// A named sheet loaded into memory
+pub struct Sheet {
+    pub name: String,
+    pub range: Range<Data>,
+}
+
+// Open a workbook from disk and load every readable sheet
+pub fn read_sheets(file_path: impl AsRef<Path>) -> Result<Vec<Sheet>> {
+    let file_data = fs::read(file_path)?;
+    read_sheets_from_bytes(file_data)
+}
+
+// Load every readable sheet of an in-memory workbook
+pub fn read_sheets_from_bytes(file_data: Vec<u8>) -> Result<Vec<Sheet>> {
+    let cursor = Cursor::new(file_data);
+    let mut workbook = open_workbook_auto_from_rs(cursor)?;
+
+    let mut sheets = Vec::new();
+    for sheet_name in workbook.sheet_names().to_owned() {
+        match workbook.worksheet_range(&sheet_name) {
+            Ok(range) => sheets.push(Sheet { name: sheet_name, range }),
+            Err(e) => {
+                // Log/skip sheets with errors
+                tracing::warn!(sheet = %sheet_name, error = %e, "Skipping unreadable sheet");
+            }
+        }
+    }
+
+    Ok(sheets)
+}
+
+// The named sheet, or the first one
+pub fn select_sheet<'a>(sheets: &'a [Sheet], name: Option<&str>) -> Result<&'a Sheet> {
+    match name {
+        Some(name) => sheets
+            .iter()
+            .find(|sheet| sheet.name == name)
+            .ok_or_else(|| Error::Invalid(format!("Sheet not found: {}", name))),
+        None => sheets
+            .first()
+            .ok_or_else(|| Error::Invalid("Workbook has no readable sheets".to_string())),
+    }
+}
+
+// Render a cell as plain text
+pub fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::String(s) => s.clone(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => {
+            if *b {
+                "TRUE".to_string()
+            } else {
+                "FALSE".to_string()
+            }
+        }
+        Data::DateTime(d) => d.to_string(),
+        _ => "".to_string(),
+    }
+}
+
+// Render a cell as text for people: like `cell_to_string`, but dates read
+// as "2024-01-31 00:00:00" rather than as Excel serial numbers
+pub fn cell_to_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(d) => match d.as_datetime() {
+            Some(naive_dt) => naive_dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => d.to_string(),
+        },
+        Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        _ => cell_to_string(cell),
+    }
+}
+
+// Read a cell as a number; numeric text counts, but not "NaN" or "inf",
+// which are more likely names than numbers
+pub fn cell_number(cell: &Data) -> Option<f64> {
+    let n = match cell {
+        Data::Float(f) => *f,
+        Data::Int(i) => *i as f64,
+        Data::String(s) => s.trim().parse().ok()?,
+        _ => return None,
+    };
+    n.is_finite().then_some(n)
+}
+
+// Read a cell as a date and time; text in one of DATETIME_FORMATS or
+// DATE_FORMAT counts
+pub fn cell_datetime(cell: &Data) -> Option<NaiveDateTime> {
+    match cell {
+        Data::DateTime(d) => d.as_datetime(),
+        Data::DateTimeIso(s) | Data::String(s) => parse_datetime(s.trim()),
+        _ => None,
+    }
+}
+
+pub fn parse_datetime(text: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, DATE_FORMAT).ok().map(|date| date.and_time(Default::default())))
+}
+
+// Write a single cell, keeping numbers, booleans and dates native
+pub fn write_cell(sheet: &mut Worksheet, row: u32, col: u16, cell: &Data) -> std::result::Result<(), XlsxError> {
+    write_cell_format(sheet, row, col, cell, None)
+}
+
+// Like `write_cell`, with a format; blank cells are written when formatted
+pub fn write_cell_format(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    cell: &Data,
+    format: Option<&Format>,
+) -> std::result::Result<(), XlsxError> {
+    match cell {
+        Data::String(s) => sheet.write_string(row, col, s, format),
+        Data::Float(f) => sheet.write_number(row, col, *f, format),
+        Data::Int(i) => sheet.write_number(row, col, *i as f64, format),
+        Data::Bool(b) => sheet.write_boolean(row, col, *b, format),
+        // The serial number as stored, shown through a date number format
+        Data::DateTime(d) => {
+            let mut date_format = format.cloned().unwrap_or_default();
+            date_format.set_num_format(if d.is_duration() { DURATION_NUM_FORMAT } else { DATETIME_NUM_FORMAT });
+            sheet.write_number(row, col, d.as_f64(), Some(&date_format))
+        }
+        Data::Error(e) => sheet.write_string(row, col, &format!("Error: {:?}", e), format),
+        _ if format.is_some() => sheet.write_blank(row, col, format),
+        _ => Ok(()),
+    }
+}
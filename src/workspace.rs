@@ -0,0 +1,199 @@
+use excel_handler::{read_pins, write_pins};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Clone)]
+pub struct FileInfo {
+    pub name: String,
+    // Pinned files are exempt from retention cleanup
+    pub pinned: bool,
+}
+
+// Files and output directory belonging to one user or team
+pub struct Workspace {
+    pub name: String,
+    pub output_dir: String,
+    pub files: Mutex<HashMap<usize, FileInfo>>,
+    pub next_id: Mutex<usize>,
+    // Names of pinned files in the output directory, mirrored to its pin list
+    pins: Mutex<HashSet<String>>,
+}
+
+impl Workspace {
+    fn new(name: String, output_dir: String) -> Self {
+        let pins = read_pins(Path::new(&output_dir)).unwrap_or_else(|e| {
+            tracing::warn!(workspace = %name, error = %e, "Failed to read pinned files");
+            HashSet::new()
+        });
+        Workspace {
+            name,
+            output_dir,
+            files: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(0),
+            pins: Mutex::new(pins),
+        }
+    }
+
+    // Registry entry for a file in the output directory, pinned if it was
+    // pinned before
+    pub fn file_info(&self, name: String) -> FileInfo {
+        let pinned = file_name(&name).is_some_and(|file_name| self.pins.lock().unwrap().contains(file_name));
+        FileInfo { name, pinned }
+    }
+
+    // Track newly written output files in the in-memory registry and
+    // return the ids they were given
+    pub fn register_files(&self, output_files: &[String]) -> Vec<usize> {
+        let mut files_map = self.files.lock().unwrap();
+        let mut next_id = self.next_id.lock().unwrap();
+        let mut ids = Vec::with_capacity(output_files.len());
+        for output_file in output_files {
+            files_map.insert(*next_id, self.file_info(output_file.clone()));
+            ids.push(*next_id);
+            *next_id += 1;
+        }
+        ids
+    }
+
+    // Pin or unpin a tracked file and save the pin list; returns false if the
+    // id is unknown
+    pub fn set_pinned(&self, index: usize, pinned: bool) -> excel_handler::Result<bool> {
+        let mut files = self.files.lock().unwrap();
+        let Some(file_info) = files.get_mut(&index) else {
+            return Ok(false);
+        };
+        if let Some(file_name) = file_name(&file_info.name) {
+            self.update_pins(file_name, pinned)?;
+        }
+        file_info.pinned = pinned;
+        Ok(true)
+    }
+
+    // Drop the pin of a file that was removed, so a later file reusing the
+    // name doesn't inherit it
+    pub fn unpin_removed(&self, path: &str) {
+        if let Some(file_name) = file_name(path) {
+            if let Err(e) = self.update_pins(file_name, false) {
+                tracing::warn!(file = %path, error = %e, "Failed to update pinned files");
+            }
+        }
+    }
+
+    fn update_pins(&self, file_name: &str, pinned: bool) -> excel_handler::Result<()> {
+        let mut pins = self.pins.lock().unwrap();
+        let changed = if pinned {
+            pins.insert(file_name.to_string())
+        } else {
+            pins.remove(file_name)
+        };
+        if changed {
+            write_pins(Path::new(&self.output_dir), &pins)?;
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &str) -> Option<&str> {
+    Path::new(path).file_name().and_then(|name| name.to_str())
+}
+
+// Subdirectory of the output directory holding the named workspaces
+const WORKSPACES_DIR: &str = "workspaces";
+
+// Isolated workspaces, created on first use. The unnamed workspace, used when
+// authentication is disabled, writes straight into the output directory;
+// named ones get their own subdirectory under `workspaces/`.
+pub struct Workspaces {
+    root_dir: String,
+    workspaces: Mutex<HashMap<String, Arc<Workspace>>>,
+}
+
+impl Workspaces {
+    pub fn new(root_dir: String) -> Self {
+        Workspaces {
+            root_dir,
+            workspaces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // False once a panic poisoned the registry lock, after which every
+    // request touching it would fail
+    pub fn is_healthy(&self) -> bool {
+        match self.workspaces.lock() {
+            Ok(workspaces) => workspaces.values().all(|workspace| !workspace.files.is_poisoned()),
+            Err(_) => false,
+        }
+    }
+
+    // Drop removed files from every registry, returning the workspace and
+    // id each one was tracked under
+    pub fn forget(&self, removed: &[String]) -> Vec<(String, usize, String)> {
+        let removed: HashSet<PathBuf> = removed.iter().map(PathBuf::from).collect();
+        let workspaces = self.workspaces.lock().unwrap();
+        let mut forgotten = Vec::new();
+        for workspace in workspaces.values() {
+            workspace.files.lock().unwrap().retain(|id, file_info| {
+                let keep = !removed.contains(&PathBuf::from(&file_info.name));
+                if !keep {
+                    forgotten.push((workspace.name.clone(), *id, file_info.name.clone()));
+                }
+                keep
+            });
+        }
+        forgotten
+    }
+
+    pub fn root_dir(&self) -> &str {
+        &self.root_dir
+    }
+
+    // Output directory of every workspace on disk, including ones nobody has
+    // used since the server started; the unnamed workspace comes first
+    pub fn output_dirs(&self) -> Vec<String> {
+        let mut output_dirs = vec![self.root_dir.clone()];
+        if let Ok(entries) = fs::read_dir(self.workspaces_dir()) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    if let Some(name) = entry.file_name().to_str() {
+                        output_dirs.push(format!("{}/{}/{}", self.root_dir, WORKSPACES_DIR, name));
+                    }
+                }
+            }
+        }
+        output_dirs
+    }
+
+    // Where the named workspaces live, which is inside the unnamed
+    // workspace's directory but not part of it
+    pub fn workspaces_dir(&self) -> PathBuf {
+        Path::new(&self.root_dir).join(WORKSPACES_DIR)
+    }
+
+    // Number of files tracked across every workspace
+    pub fn tracked_files(&self) -> usize {
+        let workspaces = self.workspaces.lock().unwrap();
+        workspaces
+            .values()
+            .map(|workspace| workspace.files.lock().unwrap().len())
+            .sum()
+    }
+
+    // Workspace names come from the validated config, so they are safe path components
+    pub fn get(&self, name: &str) -> Arc<Workspace> {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        workspaces
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let output_dir = if name.is_empty() {
+                    self.root_dir.clone()
+                } else {
+                    format!("{}/{}/{}", self.root_dir, WORKSPACES_DIR, name)
+                };
+                Arc::new(Workspace::new(name.to_string(), output_dir))
+            })
+            .clone()
+    }
+}